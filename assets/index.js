@@ -1,5 +1,8 @@
 import { importWasm } from '/wasm-raytracer/assets/loadWasm.js';
-import { render_scene } from '/wasm-raytracer/assets/pkg/wasm_raytracer.js'
+import { SceneFiles, render_scene_with_files } from '/wasm-raytracer/assets/pkg/wasm_raytracer.js'
+
+// Extra files (e.g. OBJ meshes) the scene can refer to by name
+let extraFiles = {};
 
 const DEFAULT_SCENE =
 "# Simple Sphere Scene\n" +
@@ -54,9 +57,37 @@ document.getElementById('upload-scene-file').addEventListener('change',
   reader.readAsBinaryString(event.target.files[0]);
 });
 
+document.getElementById('upload-extra-files').addEventListener('change',
+    (event) => {
+  for (let file of event.target.files) {
+    let reader = new FileReader();
+    reader.addEventListener('load', (fileEvent) => {
+      if (fileEvent.target.readyState != 2 || fileEvent.target.error) {
+        console.log('Error loading file ' + file.name);
+      } else {
+        extraFiles[file.name] = new Uint8Array(fileEvent.target.result);
+      }
+    });
+    reader.readAsArrayBuffer(file);
+  }
+});
+
 function updateSceneFile(sceneText) {
-  let b64_bytes = render_scene(sceneText);
-  document.getElementById('rendered-scene').src = "data:image/png;base64," + b64_bytes;
+  let errorText = document.getElementById('scene-error');
+  let files = new SceneFiles();
+  for (let name in extraFiles) {
+    files.add_binary_file(name, extraFiles[name]);
+  }
+  try {
+    let b64_bytes = render_scene_with_files(sceneText, files);
+    document.getElementById('rendered-scene').src = "data:image/png;base64," + b64_bytes;
+    errorText.textContent = '';
+  } catch (error) {
+    console.error('Unable to load scene: ' + error);
+    errorText.textContent = 'Unable to load scene: ' + error;
+  } finally {
+    files.free();
+  }
 }
 
 
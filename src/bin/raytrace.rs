@@ -0,0 +1,186 @@
+//! Native command-line renderer
+//!
+//! Renders a scene file and writes the result to the scene's
+//! `output_image`, picking the encoding from the file extension.
+
+extern crate wasm_raytracer;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+use wasm_raytracer::image::ImageFormat;
+use wasm_raytracer::ray_tracer::RayTracer;
+use wasm_raytracer::scene::Scene;
+
+const USAGE: &str = "\
+Usage: raytrace [OPTIONS] <SCENE>
+
+Options:
+    -r, --resolution <WIDTHxHEIGHT>  Override the scene's resolution
+    -d, --max-depth <DEPTH>          Override the scene's max ray depth
+    -o, --output <PATH>              Override the scene's output_image
+                                     (.png, .bmp or .ppm)
+    -h, --help                       Print this message";
+
+/// Command line overrides for a scene
+#[derive(Debug, Default)]
+struct Options {
+    scene_path: String,
+    resolution: Option<(usize, usize)>,
+    max_depth: Option<usize>,
+    output: Option<String>,
+}
+
+fn parse_resolution(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Invalid resolution `{}`", value);
+    let mut parts = value.splitn(2, &['x', 'X'][..]);
+    let width = parts.next().and_then(|w| w.parse::<usize>().ok());
+    let height = parts.next().and_then(|h| h.parse::<usize>().ok());
+    match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => {
+            Ok((width, height))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut scene_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "-r" | "--resolution" => {
+                options.resolution = Some(parse_resolution(&value(arg)?)?);
+            }
+            "-d" | "--max-depth" => {
+                let depth = value(arg)?;
+                options.max_depth =
+                    Some(depth.parse().map_err(|_| {
+                        format!("Invalid max depth `{}`", depth)
+                    })?);
+            }
+            "-o" | "--output" => options.output = Some(value(arg)?),
+            _ if arg.starts_with('-') => {
+                return Err(format!("Unknown option `{}`", arg))
+            }
+            _ if scene_path.is_none() => scene_path = Some(arg.clone()),
+            _ => return Err(format!("Unexpected argument `{}`", arg)),
+        }
+    }
+
+    options.scene_path =
+        scene_path.ok_or_else(|| String::from("No scene file given"))?;
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mut scene = Scene::from_file(&options.scene_path)
+        .map_err(|err| format!("{}: {}", options.scene_path, err))?;
+
+    if let Some(resolution) = options.resolution {
+        scene.set_resolution(resolution);
+    }
+    if let Some(max_depth) = options.max_depth {
+        scene.max_depth = max_depth;
+    }
+    if let Some(ref output) = options.output {
+        scene.output_image = output.clone();
+    }
+
+    // Check the format before spending time on rendering
+    let output = Path::new(&scene.output_image);
+    if ImageFormat::from_path(output).is_none() {
+        return Err(format!(
+            "Unsupported image format for {} (use .png, .bmp or .ppm)",
+            output.display()
+        ));
+    }
+
+    let image = RayTracer.render(&scene);
+    image.save(output).map_err(|err| {
+        format!("Unable to write {}: {}", output.display(), err)
+    })?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(&options) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn resolution() {
+        assert_eq!(parse_resolution("640x480"), Ok((640, 480)));
+        assert_eq!(parse_resolution("10X20"), Ok((10, 20)));
+        assert!(parse_resolution("0x5").is_err());
+        assert!(parse_resolution("640").is_err());
+        assert!(parse_resolution("640x").is_err());
+        assert!(parse_resolution("640x480x2").is_err());
+    }
+
+    #[test]
+    fn overrides() {
+        let options = parse_args(&args(&[
+            "-r",
+            "320x240",
+            "scene.scn",
+            "--max-depth",
+            "3",
+            "-o",
+            "out.png",
+        ]))
+        .unwrap();
+        assert_eq!(options.scene_path, "scene.scn");
+        assert_eq!(options.resolution, Some((320, 240)));
+        assert_eq!(options.max_depth, Some(3));
+        assert_eq!(options.output, Some(String::from("out.png")));
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let error = |given: &[&str]| parse_args(&args(given)).unwrap_err();
+        assert_eq!(error(&["-q", "scene.scn"]), "Unknown option `-q`");
+        assert_eq!(error(&["scene.scn", "-o"]), "Missing value for -o");
+        assert_eq!(
+            error(&["scene.scn", "other.scn"]),
+            "Unexpected argument `other.scn`"
+        );
+        assert_eq!(
+            error(&["-d", "deep", "scene.scn"]),
+            "Invalid max depth `deep`"
+        );
+        assert_eq!(error(&[]), "No scene file given");
+    }
+}
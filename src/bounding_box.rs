@@ -0,0 +1,106 @@
+//! An axis-aligned bounding box
+
+use std::f64;
+
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+// Slack used when comparing slab distances, so boxes that are flat along an
+// axis (e.g. around an axis-aligned triangle) are not missed due to rounding
+const SLAB_TOLERANCE: f64 = 1.0 + 1e-9;
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Default for BoundingBox {
+    /// An empty box; any union with it returns the other box
+    fn default() -> Self {
+        Self {
+            min: Vector3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector3::new(
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+        }
+    }
+}
+
+impl BoundingBox {
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+
+    /// The smallest box containing all the given points
+    pub fn from_points(points: &[Vector3]) -> Self {
+        points
+            .iter()
+            .fold(Self::default(), |bounds, &point| bounds.with_point(point))
+    }
+
+    /// Grow the box to contain a point, consuming self
+    pub fn with_point(self, point: Vector3) -> Self {
+        Self {
+            min: Vector3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            max: Vector3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        }
+    }
+
+    /// The smallest box containing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        self.with_point(other.min).with_point(other.max)
+    }
+
+    pub fn centroid(&self) -> Vector3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Index of the longest axis (0 = x, 1 = y, 2 = z)
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.max - self.min;
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Slab test; returns the entry and exit distances along the ray if it
+    /// passes through the box between `t_min` and `t_max`
+    pub fn intersects(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let mut t_enter = ray.t_min;
+        let mut t_exit = ray.t_max;
+        for axis in 0..3 {
+            let inv_direction = 1.0 / ray.direction.axis(axis);
+            let mut t0 =
+                (self.min.axis(axis) - ray.start.axis(axis)) * inv_direction;
+            let mut t1 =
+                (self.max.axis(axis) - ray.start.axis(axis)) * inv_direction;
+            if t0 > t1 {
+                ::std::mem::swap(&mut t0, &mut t1);
+            }
+            // `max` and `min` ignore NaNs, which show up when the ray starts
+            // exactly on a slab it is parallel to
+            t_enter = t_enter.max(t0);
+            t_exit = t_exit.min(t1 * SLAB_TOLERANCE);
+            if t_enter > t_exit {
+                return None;
+            }
+        }
+        Some((t_enter, t_exit))
+    }
+}
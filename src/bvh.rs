@@ -0,0 +1,287 @@
+//! Bounding volume hierarchy
+//!
+//! A binary tree of bounding boxes over a list of primitives. The BVH only
+//! knows about boxes and primitive indices; callers pass in a closure that
+//! does the actual intersection test for a given primitive.
+
+use std::fmt;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::ray::Ray;
+
+/// Maximum number of primitives in a leaf node
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug)]
+enum BvhNode {
+    Leaf {
+        bounds: BoundingBox,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bounds: BoundingBox,
+        /// The left child always directly follows its parent
+        right: usize,
+        axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &BoundingBox {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Primitive indices, reordered so each leaf owns a contiguous range
+    indices: Vec<usize>,
+}
+
+impl fmt::Debug for Bvh {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Bvh: {} nodes over {} primitives",
+            self.nodes.len(),
+            self.indices.len()
+        )
+    }
+}
+
+impl Bvh {
+    /// Build a hierarchy over `(primitive index, bounding box)` pairs
+    pub fn build(primitives: &[(usize, BoundingBox)]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * primitives.len()),
+            indices: Vec::with_capacity(primitives.len()),
+        };
+        if !primitives.is_empty() {
+            let mut primitives = primitives.to_vec();
+            bvh.build_node(&mut primitives);
+        }
+        bvh
+    }
+
+    /// Number of primitives stored in the hierarchy
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Bounds of everything in the hierarchy
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.nodes.first().map(|node| *node.bounds())
+    }
+
+    fn build_node(&mut self, primitives: &mut [(usize, BoundingBox)]) {
+        let bounds = primitives
+            .iter()
+            .fold(BoundingBox::default(), |acc, (_, b)| acc.union(b));
+
+        if primitives.len() <= MAX_LEAF_SIZE {
+            self.push_leaf(bounds, primitives);
+            return;
+        }
+
+        // Median split along the longest axis of the centroids
+        let centroid_bounds = primitives
+            .iter()
+            .fold(BoundingBox::default(), |acc, (_, b)| {
+                acc.with_point(b.centroid())
+            });
+        let axis = centroid_bounds.longest_axis();
+        if centroid_bounds.max.axis(axis) <= centroid_bounds.min.axis(axis) {
+            // All centroids coincide, so splitting can't separate anything
+            self.push_leaf(bounds, primitives);
+            return;
+        }
+        primitives.sort_by(|(_, a), (_, b)| {
+            a.centroid()
+                .axis(axis)
+                .partial_cmp(&b.centroid().axis(axis))
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+        let middle = primitives.len() / 2;
+
+        let node_index = self.nodes.len();
+        self.nodes.push(BvhNode::Interior {
+            bounds,
+            right: 0,
+            axis,
+        });
+        let (left, right) = primitives.split_at_mut(middle);
+        self.build_node(left);
+        let right_index = self.nodes.len();
+        self.build_node(right);
+        if let BvhNode::Interior { ref mut right, .. } = self.nodes[node_index]
+        {
+            *right = right_index;
+        }
+    }
+
+    fn push_leaf(
+        &mut self,
+        bounds: BoundingBox,
+        primitives: &[(usize, BoundingBox)],
+    ) {
+        self.nodes.push(BvhNode::Leaf {
+            bounds,
+            first: self.indices.len(),
+            count: primitives.len(),
+        });
+        self.indices
+            .extend(primitives.iter().map(|(index, _)| *index));
+    }
+
+    /// Find the closest intersection along the ray
+    ///
+    /// `intersect` is called with a primitive index and a copy of the ray
+    /// whose `t_max` has been shortened to the closest hit found so far.
+    pub fn closest<F>(
+        &self,
+        ray: &Ray,
+        mut intersect: F,
+    ) -> Option<(usize, Intersection)>
+    where
+        F: FnMut(usize, &Ray) -> Option<Intersection>,
+    {
+        let mut closest: Option<(usize, Intersection)> = None;
+        let mut current_ray = *ray;
+        self.traverse(&mut current_ray, |index, current_ray| {
+            if let Some(intersection) = intersect(index, current_ray) {
+                let distance = (intersection.point - ray.start).length();
+                let is_closer = match closest {
+                    Some((_, ref c)) => {
+                        distance < (c.point - ray.start).length()
+                    }
+                    None => true,
+                };
+                if is_closer {
+                    current_ray.t_max = distance;
+                    closest = Some((index, intersection));
+                }
+            }
+            false
+        });
+        closest
+    }
+
+    /// Check if any primitive intersects the ray (e.g. for shadow rays)
+    pub fn any<F>(&self, ray: &Ray, mut occludes: F) -> bool
+    where
+        F: FnMut(usize, &Ray) -> bool,
+    {
+        let mut current_ray = *ray;
+        self.traverse(&mut current_ray, |index, current_ray| {
+            occludes(index, current_ray)
+        })
+    }
+
+    /// Visit the primitives in every leaf the ray passes through, nearest
+    /// child first. Stops early and returns `true` once `visit` does.
+    fn traverse<F>(&self, ray: &mut Ray, mut visit: F) -> bool
+    where
+        F: FnMut(usize, &mut Ray) -> bool,
+    {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds().intersects(ray).is_none() {
+                continue;
+            }
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for &index in &self.indices[first..first + count] {
+                        if visit(index, ray) {
+                            return true;
+                        }
+                    }
+                }
+                BvhNode::Interior { right, axis, .. } => {
+                    let left = node_index + 1;
+                    // Push the far child first so the near one is popped
+                    // first
+                    if ray.direction.axis(axis) < 0.0 {
+                        stack.push(left);
+                        stack.push(right);
+                    } else {
+                        stack.push(right);
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    fn unit_boxes(count: usize) -> Vec<(usize, BoundingBox)> {
+        (0..count)
+            .map(|i| {
+                let offset = Vector3::new(i as f64 * 2.0, 0.0, 0.0);
+                (
+                    i,
+                    BoundingBox::new(
+                        offset,
+                        offset + Vector3::new(1.0, 1.0, 1.0),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn closest_matches_linear_scan() {
+        let boxes = unit_boxes(37);
+        let bvh = Bvh::build(&boxes);
+        assert_eq!(bvh.len(), boxes.len());
+
+        // Shoot along -x from beyond the last box; the last box is closest
+        let ray = Ray::new(
+            Vector3::new(100.0, 0.5, 0.5),
+            Vector3::new(-1.0, 0.0, 0.0),
+        );
+        let hit = bvh.closest(&ray, |index, ray| {
+            boxes[index].1.intersects(ray).map(|(t, _)| {
+                Intersection::new(
+                    Vector3::default(),
+                    ray.start + ray.direction * t,
+                )
+            })
+        });
+        assert_eq!(hit.map(|(index, _)| index), Some(36));
+    }
+
+    #[test]
+    fn any_misses_everything() {
+        let boxes = unit_boxes(10);
+        let bvh = Bvh::build(&boxes);
+        let ray =
+            Ray::new(Vector3::new(0.5, 5.0, 0.5), Vector3::new(1.0, 0.0, 0.0));
+        let mut tested = 0;
+        assert!(!bvh.any(&ray, |_, _| {
+            tested += 1;
+            true
+        }));
+        assert_eq!(tested, 0);
+    }
+}
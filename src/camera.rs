@@ -1,7 +1,75 @@
 //! A virtual camera
 
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::projections::perspective::Perspective;
+use crate::projections::projection::Projection;
+use crate::ray::Ray;
+use crate::sampler::concentric_disk;
 use crate::vector::Vector3;
 
+/// Vectors shorter than this can't give the camera a direction
+const DEGENERATE_LENGTH: f64 = 1e-9;
+
+/// Shape of the lens opening, which out of focus highlights take on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aperture {
+    Circle,
+    /// A regular polygon, like the blades of a real lens' diaphragm make.
+    /// The rotation is in radians.
+    Polygon {
+        sides: usize,
+        rotation: f64,
+    },
+}
+
+impl Aperture {
+    /// A point on an aperture of radius 1, given two numbers in [0, 1) that
+    /// choose where
+    pub fn sample(self, random: (f64, f64)) -> (f64, f64) {
+        match self {
+            Aperture::Circle => concentric_disk(random.0, random.1),
+            Aperture::Polygon { sides, rotation } => {
+                // Pick one of the triangles from the center to each side,
+                // then reuse what's left of `u` to pick a point in it
+                let scaled = random.0 * sides as f64;
+                let side = scaled.floor().min(sides as f64 - 1.0);
+                let u = (scaled - side).sqrt();
+                let v = random.1;
+                let corner = |index: f64| {
+                    let angle = rotation + 2.0 * PI * index / sides as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(side), corner(side + 1.0));
+                (
+                    u * ((1.0 - v) * a.0 + v * b.0),
+                    u * ((1.0 - v) * a.1 + v * b.1),
+                )
+            }
+        }
+    }
+}
+
+/// Which way across the image a field of view is measured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FovAxis {
+    Horizontal,
+    Vertical,
+}
+
+impl FromStr for FovAxis {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "horizontal" | "h" => Ok(FovAxis::Horizontal),
+            "vertical" | "v" => Ok(FovAxis::Vertical),
+            _ => Err(()),
+        }
+    }
+}
+
 /// A virtual camera
 #[derive(Debug)]
 pub struct Camera {
@@ -10,6 +78,17 @@ pub struct Camera {
     pub up: Vector3,
     pub right: Vector3,
     pub vert_half_angle: f64,
+    /// The field of view across the image, when it was given that way, so
+    /// `vert_half_angle` can follow changes to the image's aspect ratio
+    pub horizontal_fov: Option<f64>,
+    /// Radius of the lens, or 0 for a pinhole camera where everything is in
+    /// focus
+    pub aperture_radius: f64,
+    /// Distance along `direction` at which things are in focus
+    pub focal_distance: f64,
+    pub aperture: Aperture,
+    /// How rays spread out from the camera
+    pub projection: Box<Projection>,
 }
 
 impl Default for Camera {
@@ -20,6 +99,11 @@ impl Default for Camera {
             up: Vector3::new(0.0, 1.0, 0.0),
             right: Vector3::new(1.0, 0.0, 0.0),
             vert_half_angle: 45.0_f64.to_radians(),
+            horizontal_fov: None,
+            aperture_radius: 0.0,
+            focal_distance: 1.0,
+            aperture: Aperture::Circle,
+            projection: Box::new(Perspective),
         }
     }
 }
@@ -41,6 +125,185 @@ impl Camera {
             up,
             right,
             vert_half_angle: parameters[9].to_radians(),
+            horizontal_fov: None,
+            aperture_radius: 0.0,
+            focal_distance: 1.0,
+            aperture: Aperture::Circle,
+            projection: Box::new(Perspective),
+        }
+    }
+
+    /// A camera at `eye` looking towards `target`, with `up` pointing up
+    /// in the image as closely as it can. Fails when the view direction
+    /// can't be worked out, or `up` is zero or parallel to it.
+    pub fn look_at(
+        eye: Vector3,
+        target: Vector3,
+        up: Vector3,
+        vert_half_angle: f64,
+    ) -> Result<Self, String> {
+        let direction = target - eye;
+        if direction.length() < DEGENERATE_LENGTH {
+            return Err(String::from("eye and target are the same point"));
+        }
+        if up.length() < DEGENERATE_LENGTH {
+            return Err(String::from("up vector is zero"));
+        }
+        let (direction, up) = (direction.normalized(), up.normalized());
+        let right = up.cross(&direction);
+        if right.length() < DEGENERATE_LENGTH {
+            return Err(String::from(
+                "up vector is parallel to the view direction",
+            ));
+        }
+        let right = right.normalized();
+        Ok(Self {
+            position: eye,
+            direction,
+            up: direction.cross(&right),
+            right,
+            vert_half_angle,
+            ..Self::default()
+        })
+    }
+
+    /// The vertical half angle giving a field of view of `angle` radians
+    /// across `axis`, in an image `aspect` times as wide as it is high
+    pub fn vert_half_angle(angle: f64, axis: FovAxis, aspect: f64) -> f64 {
+        match axis {
+            FovAxis::Vertical => angle / 2.0,
+            FovAxis::Horizontal => ((angle / 2.0).tan() / aspect).atan(),
+        }
+    }
+
+    /// Keep a horizontal field of view when the image is now `aspect` times
+    /// as wide as it is high
+    pub fn fit_aspect(&mut self, aspect: f64) {
+        if let Some(fov) = self.horizontal_fov {
+            self.vert_half_angle =
+                Self::vert_half_angle(fov, FovAxis::Horizontal, aspect);
+        }
+    }
+
+    /// Take the lens and projection of another camera, keeping this one's
+    /// position, orientation and field of view
+    pub fn with_optics_of(self, other: Camera) -> Self {
+        self.with_lens(
+            other.aperture_radius,
+            other.focal_distance,
+            other.aperture,
+        )
+        .with_projection(other.projection)
+    }
+
+    /// Give the camera a thin lens, so only things at `focal_distance` are
+    /// in focus
+    pub fn with_lens(
+        mut self,
+        aperture_radius: f64,
+        focal_distance: f64,
+        aperture: Aperture,
+    ) -> Self {
+        self.aperture_radius = aperture_radius;
+        self.focal_distance = focal_distance;
+        self.aperture = aperture;
+        self
+    }
+
+    /// Use a different projection than perspective
+    pub fn with_projection(mut self, projection: Box<Projection>) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// The ray from the eye through a point on the image plane, if there is
+    /// one
+    ///
+    /// `row` and `col` are pixel coordinates, where whole numbers are the
+    /// centers of pixels.
+    pub fn primary_ray(
+        &self,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray> {
+        self.projection.primary_ray(self, resolution, row, col)
+    }
+
+    /// The ray from a point on the lens through a point on the image plane,
+    /// bent so that all rays through the same point meet on the focal plane
+    ///
+    /// `lens` chooses the point on the lens, as two numbers in [0, 1).
+    pub fn lens_ray(
+        &self,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+        lens: (f64, f64),
+    ) -> Option<Ray> {
+        let pinhole = self.primary_ray(resolution, row, col)?;
+        if self.aperture_radius <= 0.0 {
+            return Some(pinhole);
+        }
+        let focus = self.projection.focus(self, &pinhole);
+        let (x, y) = self.aperture.sample(lens);
+        let start = pinhole.start
+            + (self.right * x + self.up * y) * self.aperture_radius;
+        Some(Ray::new(start, focus - start))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_matches_camera_parameters() {
+        let eye = Vector3::new(1.0, 2.0, 3.0);
+        let camera = Camera::look_at(
+            eye,
+            Vector3::new(1.0, 0.0, 5.0),
+            Vector3::new(0.0, 3.0, 0.0),
+            20.0_f64.to_radians(),
+        )
+        .unwrap();
+        let expected = Camera::from_parameters(&[
+            1.0, 2.0, 3.0, 0.0, -1.0, 1.0, 0.0, 1.0, 0.0, 20.0,
+        ]);
+        for &(a, b) in &[
+            (camera.direction, expected.direction),
+            (camera.up, expected.up),
+            (camera.right, expected.right),
+        ] {
+            assert!((a - b).length() < 1e-12, "{:?} != {:?}", a, b);
+        }
+
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        assert!(Camera::look_at(eye, eye, up, 0.5).is_err());
+        assert!(Camera::look_at(eye, eye + up * 2.0, up, 0.5).is_err());
+        let zero = Vector3::new(0.0, 0.0, 0.0);
+        assert!(Camera::look_at(eye, zero, zero, 0.5).is_err());
+    }
+
+    #[test]
+    fn lens_rays_meet_on_the_focal_plane() {
+        let camera = Camera::default().with_lens(
+            0.5,
+            4.0,
+            Aperture::Polygon {
+                sides: 6,
+                rotation: 0.3,
+            },
+        );
+        let pinhole = camera.primary_ray((40, 30), 7.0, 31.0).unwrap();
+        let focus =
+            pinhole.start + pinhole.direction * (4.0 / pinhole.direction.z);
+        for &lens in &[(0.1, 0.2), (0.5, 0.9), (0.95, 0.4)] {
+            let ray = camera.lens_ray((40, 30), 7.0, 31.0, lens).unwrap();
+            assert!(ray.start.z == 0.0);
+            assert!((ray.start - camera.position).length() <= 0.5);
+            let t = (focus.z - ray.start.z) / ray.direction.z;
+            assert!((ray.start + ray.direction * t - focus).length() < 1e-9);
         }
     }
 }
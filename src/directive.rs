@@ -0,0 +1,184 @@
+//! A single line of a scene file, split into tokens
+//!
+//! Keeps track of where each token came from so parse errors can point at
+//! the exact line and column.
+
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::scene_error::SceneError;
+
+#[derive(Debug)]
+pub struct Directive<'a> {
+    /// Line number in the scene file (1-based)
+    pub line: usize,
+
+    /// The directive name (the first token on the line)
+    pub name: &'a str,
+
+    /// The remaining tokens, along with their (1-based) columns
+    arguments: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Directive<'a> {
+    /// Tokenize a line, ignoring anything after a `#`. Returns `None` for
+    /// blank and comment-only lines.
+    pub fn parse(line: usize, text: &'a str) -> Option<Self> {
+        let text = match text.find('#') {
+            Some(index) => &text[..index],
+            None => text,
+        };
+
+        let mut tokens = Vec::new();
+        let mut token_start = None;
+        for (index, c) in text.char_indices() {
+            match (c.is_whitespace(), token_start) {
+                (false, None) => token_start = Some(index),
+                (true, Some(start)) => {
+                    tokens.push((start, &text[start..index]));
+                    token_start = None;
+                }
+                _ => (),
+            }
+        }
+        if let Some(start) = token_start {
+            tokens.push((start, &text[start..]));
+        }
+
+        if tokens.is_empty() {
+            return None;
+        }
+        let columns: Vec<_> = tokens
+            .into_iter()
+            .map(|(start, token)| (text[..start].chars().count() + 1, token))
+            .collect();
+        Some(Self {
+            line,
+            name: columns[0].1,
+            arguments: columns[1..].to_vec(),
+        })
+    }
+
+    /// Number of arguments (not counting the directive name)
+    pub fn len(&self) -> usize {
+        self.arguments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arguments.is_empty()
+    }
+
+    /// The raw argument at `index`
+    pub fn argument(&self, index: usize) -> Result<&'a str, SceneError> {
+        self.arguments
+            .get(index)
+            .map(|&(_, token)| token)
+            .ok_or_else(|| {
+                self.error(format!("missing argument {}", index + 1))
+            })
+    }
+
+    /// Require exactly `count` arguments
+    pub fn expect_arguments(&self, count: usize) -> Result<(), SceneError> {
+        self.expect_one_of(&[count])
+    }
+
+    /// Require one of the given numbers of arguments
+    pub fn expect_one_of(&self, counts: &[usize]) -> Result<(), SceneError> {
+        if counts.contains(&self.len()) {
+            return Ok(());
+        }
+        let expected = counts
+            .iter()
+            .map(|count| count.to_string())
+            .collect::<Vec<_>>()
+            .join(" or ");
+        let error = self.error(format!(
+            "expected {} arguments but found {}",
+            expected,
+            self.len()
+        ));
+        // Point at the first extra token, if there is one
+        match self
+            .arguments
+            .get(counts.iter().cloned().max().unwrap_or(0))
+        {
+            Some(&(column, token)) => Err(error.at_token(column, token)),
+            None => Err(error),
+        }
+    }
+
+    /// Parse the argument at `index`
+    pub fn parse_argument<T: FromStr>(
+        &self,
+        index: usize,
+        kind: &str,
+    ) -> Result<T, SceneError> {
+        let (column, token) = match self.arguments.get(index) {
+            Some(&argument) => argument,
+            None => {
+                return Err(
+                    self.error(format!("missing argument {}", index + 1))
+                )
+            }
+        };
+        token.parse::<T>().map_err(|_| {
+            self.error(format!("argument {} is not {}", index + 1, kind))
+                .at_token(column, token)
+        })
+    }
+
+    /// Parse a range of arguments as floating point numbers
+    pub fn floats(&self, range: Range<usize>) -> Result<Vec<f64>, SceneError> {
+        range
+            .map(|index| self.parse_argument::<f64>(index, "a number"))
+            .collect()
+    }
+
+    /// Parse a range of arguments as non-negative integers
+    pub fn integers(
+        &self,
+        range: Range<usize>,
+    ) -> Result<Vec<usize>, SceneError> {
+        range
+            .map(|index| {
+                self.parse_argument::<usize>(index, "a non-negative integer")
+            })
+            .collect()
+    }
+
+    /// Parse an index into a list of `count` already defined items
+    pub fn index(
+        &self,
+        index: usize,
+        count: usize,
+        what: &str,
+    ) -> Result<usize, SceneError> {
+        let value = self.parse_argument::<usize>(index, "an index")?;
+        if value < count {
+            Ok(value)
+        } else {
+            Err(self
+                .error(format!(
+                    "{} index {} is out of range ({} defined)",
+                    what, value, count
+                ))
+                .at_token(self.arguments[index].0, self.arguments[index].1))
+        }
+    }
+
+    /// An error about this directive as a whole
+    pub fn error(&self, message: String) -> SceneError {
+        SceneError::new(self.line, self.name, message)
+    }
+
+    /// An error about a particular argument
+    pub fn argument_error(&self, index: usize, message: String) -> SceneError {
+        match self.arguments.get(index) {
+            Some(&(column, token)) => {
+                self.error(message).at_token(column, token)
+            }
+            None => self.error(message),
+        }
+    }
+}
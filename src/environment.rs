@@ -0,0 +1,298 @@
+//! Environment maps: light arriving from far away, looked up by direction in
+//! an image
+//!
+//! Rays that leave the scene (camera rays, reflections and refractions) see
+//! the environment instead of the flat background color. An
+//! `EnvironmentLight` can also light the scene with it.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::pixel::Pixel;
+use crate::vector::Vector3;
+
+/// How directions are laid out in the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnvironmentLayout {
+    /// Longitude across, from -z through -x to +z in the middle, then +x
+    /// and back to -z; latitude down, from +y to -y
+    Equirectangular,
+    /// Six square faces in a horizontal cross, 4 faces wide and 3 high: -x,
+    /// +z, +x and -z across the middle, with +y above and -y below +z
+    Cube,
+}
+
+impl FromStr for EnvironmentLayout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equirect" | "equirectangular" | "latlong" => {
+                Ok(EnvironmentLayout::Equirectangular)
+            }
+            "cube" | "cubemap" => Ok(EnvironmentLayout::Cube),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Environment {
+    pub image: Arc<Image>,
+    pub layout: EnvironmentLayout,
+    /// Rotation about the y axis, in radians
+    pub rotation: f64,
+    /// Scale for the image's colors
+    pub intensity: f64,
+}
+
+impl Environment {
+    /// Check the image has the right shape for the layout
+    pub fn new(
+        image: Arc<Image>,
+        layout: EnvironmentLayout,
+        rotation: f64,
+        intensity: f64,
+    ) -> Result<Self, String> {
+        if image.width == 0 || image.height == 0 {
+            return Err(String::from("environment image is empty"));
+        }
+        // 4:3 also means the width is a multiple of 4
+        if layout == EnvironmentLayout::Cube
+            && image.width * 3 != image.height * 4
+        {
+            return Err(format!(
+                "cube map must be 4 square faces wide and 3 high, not {}x{}",
+                image.width, image.height
+            ));
+        }
+        Ok(Self {
+            image,
+            layout,
+            rotation,
+            intensity,
+        })
+    }
+
+    /// Light arriving from `direction` (towards the viewer, so the opposite
+    /// of the way it travels)
+    pub fn color(&self, direction: Vector3) -> Pixel {
+        let direction = rotate(direction.normalized(), -self.rotation);
+        let (x, y, region) = match self.layout {
+            EnvironmentLayout::Equirectangular => {
+                let u = 0.5 + direction.x.atan2(direction.z) / (2.0 * PI);
+                let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+                let (width, height) = (self.image.width, self.image.height);
+                (u * width as f64, v * height as f64, (0, 0, width, height))
+            }
+            EnvironmentLayout::Cube => {
+                let size = self.image.width / 4;
+                let (face, a, b) = cube_face(direction);
+                let (col, row) = CUBE_FACES[face];
+                (
+                    (col as f64 + (a + 1.0) / 2.0) * size as f64,
+                    (row as f64 + (b + 1.0) / 2.0) * size as f64,
+                    (col * size, row * size, size, size),
+                )
+            }
+        };
+        Pixel::from_pix_unclamped(self.bilinear(x, y, region)) * self.intensity
+    }
+
+    /// The direction a point in the image looks towards, if any. `x` and `y`
+    /// are in texels, from the top left corner.
+    pub fn direction_at(&self, x: f64, y: f64) -> Option<Vector3> {
+        let direction = match self.layout {
+            EnvironmentLayout::Equirectangular => {
+                let phi = (x / self.image.width as f64 - 0.5) * 2.0 * PI;
+                let theta = y / self.image.height as f64 * PI;
+                Vector3::new(
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                    theta.sin() * phi.cos(),
+                )
+            }
+            EnvironmentLayout::Cube => {
+                let size = (self.image.width / 4) as f64;
+                let (col, row) = ((x / size).floor(), (y / size).floor());
+                let face =
+                    CUBE_FACES.iter().position(|&(face_col, face_row)| {
+                        (face_col as f64, face_row as f64) == (col, row)
+                    })?;
+                let a = 2.0 * (x / size - col) - 1.0;
+                let b = 2.0 * (y / size - row) - 1.0;
+                cube_direction(face, a, b).normalized()
+            }
+        };
+        Some(rotate(direction, self.rotation))
+    }
+
+    /// Solid angle covered by a texel at a point in the image
+    pub fn texel_solid_angle(&self, x: f64, y: f64) -> f64 {
+        match self.layout {
+            EnvironmentLayout::Equirectangular => {
+                let (width, height) =
+                    (self.image.width as f64, self.image.height as f64);
+                let theta = y / height * PI;
+                (2.0 * PI / width) * (PI / height) * theta.sin()
+            }
+            EnvironmentLayout::Cube => {
+                let size = (self.image.width / 4) as f64;
+                let a = 2.0 * (x / size).fract() - 1.0;
+                let b = 2.0 * (y / size).fract() - 1.0;
+                (2.0 / size).powi(2) / (1.0 + a * a + b * b).powf(1.5)
+            }
+        }
+    }
+
+    /// Bilinear filtering, staying inside a region of the image
+    fn bilinear(
+        &self,
+        x: f64,
+        y: f64,
+        region: (usize, usize, usize, usize),
+    ) -> Pixel {
+        let (left, top, width, height) = region;
+        let right = (left + width - 1) as f64;
+        let bottom = (top + height - 1) as f64;
+        // Continuous texel coordinates, with texel centers at .5
+        let x = (x - 0.5).clamp(left as f64, right);
+        let y = (y - 0.5).clamp(top as f64, bottom);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x1, y1) = ((x0 + 1.0).min(right), (y0 + 1.0).min(bottom));
+
+        let mut sum = Pixel::from_rgba_unclamped(0.0, 0.0, 0.0, 1.0);
+        let corners = [
+            (x0, y0, (1.0 - fx) * (1.0 - fy)),
+            (x1, y0, fx * (1.0 - fy)),
+            (x0, y1, (1.0 - fx) * fy),
+            (x1, y1, fx * fy),
+        ];
+        for &(x, y, weight) in &corners {
+            if let Some(texel) = self.image.get_pixel(y as usize, x as usize) {
+                sum.r += texel.r * weight;
+                sum.g += texel.g * weight;
+                sum.b += texel.b * weight;
+            }
+        }
+        sum
+    }
+}
+
+/// Column and row of each face in a cube map cross: +x, -x, +y, -y, +z, -z
+const CUBE_FACES: [(usize, usize); 6] =
+    [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+/// The cube face a direction points at, and where on it, each from -1 to 1
+/// (left to right and top to bottom)
+fn cube_face(direction: Vector3) -> (usize, f64, f64) {
+    let Vector3 { x, y, z } = direction;
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z / ax, -y / ax)
+        } else {
+            (1, z / ax, -y / ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x / ay, z / ay)
+        } else {
+            (3, x / ay, -z / ay)
+        }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+/// The (unnormalized) direction through a point on a cube face
+fn cube_direction(face: usize, a: f64, b: f64) -> Vector3 {
+    match face {
+        0 => Vector3::new(1.0, -b, -a),
+        1 => Vector3::new(-1.0, -b, a),
+        2 => Vector3::new(a, 1.0, b),
+        3 => Vector3::new(a, -1.0, -b),
+        4 => Vector3::new(a, -b, 1.0),
+        _ => Vector3::new(-a, -b, -1.0),
+    }
+}
+
+/// Rotate a direction about the y axis
+fn rotate(direction: Vector3, angle: f64) -> Vector3 {
+    let (sin, cos) = angle.sin_cos();
+    Vector3::new(
+        direction.x * cos + direction.z * sin,
+        direction.y,
+        -direction.x * sin + direction.z * cos,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn directions_round_trip() {
+        let directions = [
+            Vector3::new(0.3, 0.5, 0.8).normalized(),
+            Vector3::new(-0.9, -0.2, 0.1).normalized(),
+            Vector3::new(0.1, -0.95, -0.3).normalized(),
+            Vector3::new(-0.2, 0.3, -0.9).normalized(),
+        ];
+        for &layout in
+            &[EnvironmentLayout::Equirectangular, EnvironmentLayout::Cube]
+        {
+            let image = Arc::new(Image::new(8, 6));
+            let environment =
+                Environment::new(image, layout, 0.7, 1.0).unwrap();
+            for &direction in &directions {
+                // Find the point in the image the same way `color` does
+                let local = rotate(direction, -environment.rotation);
+                let (x, y) = match layout {
+                    EnvironmentLayout::Equirectangular => (
+                        (0.5 + local.x.atan2(local.z) / (2.0 * PI)) * 8.0,
+                        local.y.acos() / PI * 6.0,
+                    ),
+                    EnvironmentLayout::Cube => {
+                        let (face, a, b) = cube_face(local);
+                        let (col, row) = CUBE_FACES[face];
+                        (
+                            (col as f64 + (a + 1.0) / 2.0) * 2.0,
+                            (row as f64 + (b + 1.0) / 2.0) * 2.0,
+                        )
+                    }
+                };
+                assert_close(
+                    environment.direction_at(x, y).unwrap(),
+                    direction,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cube_faces_meet() {
+        // Each face's right edge is the next one's left edge
+        let faces = [1, 4, 0, 5, 1];
+        for pair in faces.windows(2) {
+            for &b in &[-0.5, 0.0, 0.7] {
+                assert_close(
+                    cube_direction(pair[0], 1.0, b),
+                    cube_direction(pair[1], -1.0, b),
+                );
+            }
+        }
+        // +y and -y meet +z at its top and bottom
+        assert_close(cube_direction(2, 0.2, 1.0), cube_direction(4, 0.2, -1.0));
+        assert_close(cube_direction(3, 0.2, -1.0), cube_direction(4, 0.2, 1.0));
+    }
+}
@@ -0,0 +1,117 @@
+//! How much light a dielectric (glass, water, ...) surface reflects
+//!
+//! The rest of the light is transmitted. Reflection gets stronger at
+//! grazing angles, which is what makes glass look like glass.
+
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Fresnel {
+    /// Fixed reflection and transmission, set by the material's colors
+    #[default]
+    Off,
+    /// Schlick's approximation
+    Schlick,
+    /// The exact Fresnel equations for unpolarized light
+    Exact,
+}
+
+impl FromStr for Fresnel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" | "none" => Ok(Fresnel::Off),
+            "schlick" => Ok(Fresnel::Schlick),
+            "exact" | "dielectric" => Ok(Fresnel::Exact),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Fresnel {
+    /// Fraction of light reflected when going from a medium with index of
+    /// refraction `eta_incident` into one with `eta_transmitted`, at an
+    /// angle with cosine `cos_incident` from the normal. `None` when the
+    /// mode is `Off`.
+    pub fn reflectance(
+        self,
+        cos_incident: f64,
+        eta_incident: f64,
+        eta_transmitted: f64,
+    ) -> Option<f64> {
+        let cos_incident = cos_incident.abs().min(1.0);
+        let sin_transmitted = eta_incident / eta_transmitted
+            * (1.0 - cos_incident * cos_incident).sqrt();
+        if self != Fresnel::Off && sin_transmitted >= 1.0 {
+            // Total internal reflection
+            return Some(1.0);
+        }
+        let cos_transmitted = (1.0 - sin_transmitted * sin_transmitted).sqrt();
+
+        match self {
+            Fresnel::Off => None,
+            Fresnel::Schlick => {
+                let r0 = ((eta_incident - eta_transmitted)
+                    / (eta_incident + eta_transmitted))
+                    .powi(2);
+                // Use the angle on the less dense side
+                let cos = if eta_incident <= eta_transmitted {
+                    cos_incident
+                } else {
+                    cos_transmitted
+                };
+                Some(r0 + (1.0 - r0) * (1.0 - cos).powi(5))
+            }
+            Fresnel::Exact => {
+                let parallel = (eta_transmitted * cos_incident
+                    - eta_incident * cos_transmitted)
+                    / (eta_transmitted * cos_incident
+                        + eta_incident * cos_transmitted);
+                let perpendicular = (eta_incident * cos_incident
+                    - eta_transmitted * cos_transmitted)
+                    / (eta_incident * cos_incident
+                        + eta_transmitted * cos_transmitted);
+                Some(
+                    (parallel * parallel + perpendicular * perpendicular) / 2.0,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn normal_incidence_and_total_internal_reflection() {
+        for &mode in &[Fresnel::Schlick, Fresnel::Exact] {
+            // Air to glass, head on: ((1 - 1.5) / (1 + 1.5))^2
+            assert_close(mode.reflectance(1.0, 1.0, 1.5).unwrap(), 0.04);
+            // Glass to air past the critical angle (about 41.8 degrees)
+            let cos = 45f64.to_radians().cos();
+            assert_close(mode.reflectance(cos, 1.5, 1.0).unwrap(), 1.0);
+            // Grazing
+            assert_close(mode.reflectance(0.0, 1.0, 1.5).unwrap(), 1.0);
+        }
+        assert_eq!(Fresnel::Off.reflectance(1.0, 1.0, 1.5), None);
+    }
+
+    #[test]
+    fn exact_at_brewster_angle() {
+        // Only the perpendicular part is reflected at Brewster's angle
+        let brewster = 1.5f64.atan();
+        let cos_t = (1.0 - (brewster.sin() / 1.5).powi(2)).sqrt();
+        let cos_i = brewster.cos();
+        let perpendicular = (cos_i - 1.5 * cos_t) / (cos_i + 1.5 * cos_t);
+        assert_close(
+            Fresnel::Exact.reflectance(cos_i, 1.0, 1.5).unwrap(),
+            perpendicular * perpendicular / 2.0,
+        );
+    }
+}
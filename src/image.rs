@@ -4,12 +4,35 @@
 // Updateded by Stephen J. Guy, 2017
 // Translated to Rust by Bridger Herman, 2018
 
-use std::io::BufWriter;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 
 use png::HasParameters;
 
 use crate::pixel::{Pixel, RawPixel};
 
+/// File formats an image can be saved as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Bmp,
+    Ppm,
+}
+
+impl ImageFormat {
+    /// Pick a format based on a file's extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "bmp" => Some(ImageFormat::Bmp),
+            "ppm" => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
 /// A struct representing a collection of pixels
 #[derive(Debug, Clone)]
 pub struct Image {
@@ -17,7 +40,6 @@ pub struct Image {
 
     pub width: usize,
     pub height: usize,
-    // sampling_method:
 }
 
 impl Image {
@@ -37,6 +59,57 @@ impl Image {
         }
     }
 
+    /// Decode a PNG file. Any bit depth and color type is accepted;
+    /// everything is converted to 8-bit RGBA.
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let decoder = png::Decoder::new(bytes);
+        let (info, mut reader) = decoder
+            .read_info()
+            .map_err(|err| format!("Unable to decode PNG: {}", err))?;
+        let mut data = vec![0; info.buffer_size()];
+        reader
+            .next_frame(&mut data)
+            .map_err(|err| format!("Unable to decode PNG: {}", err))?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            png::ColorType::Indexed => {
+                return Err(String::from("Unable to expand indexed PNG"))
+            }
+        };
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in data.chunks(info.line_size).take(height) {
+            for sample in row.chunks(channels).take(width) {
+                pixels.push(match *sample {
+                    [v] => RawPixel {
+                        r: v,
+                        g: v,
+                        b: v,
+                        a: 255,
+                    },
+                    [v, a] => RawPixel {
+                        r: v,
+                        g: v,
+                        b: v,
+                        a,
+                    },
+                    [r, g, b] => RawPixel { r, g, b, a: 255 },
+                    [r, g, b, a] => RawPixel { r, g, b, a },
+                    _ => unreachable!(),
+                });
+            }
+        }
+        Ok(Self {
+            pixels,
+            width,
+            height,
+        })
+    }
+
     /// Add a background to an image (overwrites image)
     pub fn with_background(mut self, color: Pixel) -> Image {
         self.pixels = vec![RawPixel::from(color); self.pixels.len()];
@@ -112,4 +185,96 @@ impl Image {
 
         w.into_inner().expect("Unable to get png byte vector")
     }
+
+    /// Uncompressed 24-bit BMP (alpha is dropped)
+    pub fn to_bmp_bytes(&self) -> Vec<u8> {
+        const HEADER_SIZE: usize = 14 + 40;
+        // Each row is padded to a multiple of 4 bytes
+        let row_size = (3 * self.width + 3) & !3;
+        let data_size = row_size * self.height;
+        let file_size = HEADER_SIZE + data_size;
+
+        let mut bytes = Vec::with_capacity(file_size);
+        // File header
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&(file_size as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        // Info header
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&(self.width as i32).to_le_bytes());
+        bytes.extend_from_slice(&(self.height as i32).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&24u16.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(data_size as u32).to_le_bytes());
+        // 72 DPI, in pixels per meter
+        bytes.extend_from_slice(&2835i32.to_le_bytes());
+        bytes.extend_from_slice(&2835i32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+
+        // Rows are stored bottom to top, in BGR order
+        for row in (0..self.height).rev() {
+            let start = bytes.len();
+            for raw in &self.pixels[row * self.width..(row + 1) * self.width] {
+                bytes.extend_from_slice(&[raw.b, raw.g, raw.r]);
+            }
+            bytes.resize(start + row_size, 0);
+        }
+        bytes
+    }
+
+    /// Binary (P6) PPM (alpha is dropped)
+    pub fn to_ppm_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for raw in &self.pixels {
+            bytes.extend_from_slice(&[raw.r, raw.g, raw.b]);
+        }
+        bytes
+    }
+
+    /// Encode the image in the given format
+    pub fn to_format_bytes(&self, format: ImageFormat) -> Vec<u8> {
+        match format {
+            ImageFormat::Png => self.to_png_bytes(),
+            ImageFormat::Bmp => self.to_bmp_bytes(),
+            ImageFormat::Ppm => self.to_ppm_bytes(),
+        }
+    }
+
+    /// Write the image to a file, picking the encoding from the file's
+    /// extension. Missing parent directories are created.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let format = ImageFormat::from_path(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Unsupported image format for {} (use .png, .bmp or .ppm)",
+                    path.display()
+                ),
+            )
+        })?;
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        File::create(path)?.write_all(&self.to_format_bytes(format))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trip() {
+        let mut image = Image::new(3, 2);
+        image.set_pixel(0, 0, Pixel::from_rgb(1.0, 0.0, 0.0));
+        image.set_pixel(1, 2, Pixel::from_rgba(0.0, 0.0, 1.0, 0.0));
+        let decoded = Image::from_png_bytes(&image.to_png_bytes()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (3, 2));
+        assert_eq!(decoded.to_bytes(), image.to_bytes());
+    }
 }
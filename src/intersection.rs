@@ -6,6 +6,21 @@ use crate::vector::Vector3;
 pub struct Intersection {
     pub surface_normal: Vector3,
     pub point: Vector3,
+
+    /// The normal of the surface itself, ignoring smooth shading. This is
+    /// the one to trust for which side of the surface is outside.
+    pub face_normal: Vector3,
+
+    /// For triangles, the weights of the first, second and third vertex
+    /// that give the hit point. Use these to interpolate vertex attributes.
+    pub barycentric: Option<[f64; 3]>,
+
+    /// Texture coordinates of the hit point
+    pub uv: (f64, f64),
+
+    /// The hit point relative to the object, so that solid textures move
+    /// along with it
+    pub local_point: Vector3,
 }
 
 impl Intersection {
@@ -13,6 +28,30 @@ impl Intersection {
         Self {
             surface_normal,
             point,
+            face_normal: surface_normal,
+            barycentric: None,
+            uv: (0.0, 0.0),
+            local_point: point,
         }
     }
+
+    pub fn with_face_normal(mut self, face_normal: Vector3) -> Self {
+        self.face_normal = face_normal;
+        self
+    }
+
+    pub fn with_barycentric(mut self, barycentric: [f64; 3]) -> Self {
+        self.barycentric = Some(barycentric);
+        self
+    }
+
+    pub fn with_uv(mut self, uv: (f64, f64)) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_local_point(mut self, local_point: Vector3) -> Self {
+        self.local_point = local_point;
+        self
+    }
 }
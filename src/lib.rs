@@ -1,23 +1,41 @@
 extern crate base64;
 extern crate png;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 extern crate wasm_bindgen;
 #[macro_use]
 extern crate log;
 extern crate wasm_logger;
 
+pub mod bounding_box;
+pub mod bvh;
 pub mod camera;
+pub mod directive;
+pub mod environment;
+pub mod fresnel;
 pub mod image;
 pub mod intersection;
 pub mod lights;
 pub mod material;
+pub mod matrix;
+pub mod obj;
 pub mod objects;
+pub mod path_tracer;
 pub mod pixel;
+pub mod polynomial;
+pub mod projections;
+pub mod random;
 pub mod ray;
 pub mod ray_tracer;
+pub mod resources;
+pub mod sampler;
 pub mod scene;
+pub mod scene_error;
+pub mod textures;
 pub mod vector;
 
 use ray_tracer::RayTracer;
+use resources::Resources;
 use scene::Scene;
 
 use wasm_bindgen::prelude::*;
@@ -30,13 +48,50 @@ pub fn main() -> Result<(), JsValue> {
     Ok(())
 }
 
+/// Files a scene refers to (e.g. OBJ meshes), keyed by the name the scene
+/// uses for them
 #[wasm_bindgen]
-pub fn render_scene(scene_contents: String) -> String {
-    let scene = Scene::from_text(scene_contents);
+#[derive(Default)]
+pub struct SceneFiles {
+    resources: Resources,
+}
+
+#[wasm_bindgen]
+impl SceneFiles {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_text_file(&mut self, name: String, contents: String) {
+        self.resources.add_file(&name, contents.into_bytes());
+    }
+
+    pub fn add_binary_file(&mut self, name: String, contents: Vec<u8>) {
+        self.resources.add_file(&name, contents);
+    }
+}
+
+/// Render a scene, returning a base64-encoded PNG. If the scene can't be
+/// loaded, the error message is thrown to JavaScript instead.
+#[wasm_bindgen]
+pub fn render_scene(scene_contents: String) -> Result<String, JsValue> {
+    render_scene_with_files(scene_contents, &SceneFiles::new())
+}
+
+/// Render a scene that refers to other files (e.g. `obj mesh.obj`)
+#[wasm_bindgen]
+pub fn render_scene_with_files(
+    scene_contents: String,
+    files: &SceneFiles,
+) -> Result<String, JsValue> {
+    let scene =
+        Scene::from_text_with_resources(scene_contents, &files.resources)
+            .map_err(|err| JsValue::from(err.to_string()))?;
 
     let rt = RayTracer;
     let image = rt.render(&scene);
 
     let bytes = image.to_png_bytes();
-    base64::encode(&bytes)
+    Ok(base64::encode(&bytes))
 }
@@ -0,0 +1,176 @@
+//! Area light. Light comes from a whole shape instead of a single point, so
+//! shadows get soft edges.
+//!
+//! The color is the light's total intensity, as for a point light: far away,
+//! an area light looks like a point light of the same color. Each shadow
+//! sample carries an equal share of it.
+
+use std::f64::consts::PI;
+
+use crate::camera::Camera;
+use crate::intersection::Intersection;
+use crate::lights::light::{Light, LightSample};
+use crate::material::Material;
+use crate::pixel::Pixel;
+use crate::sampler::{concentric_disk, tangents};
+use crate::vector::Vector3;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightShape {
+    /// A parallelogram spanned by two edges from a corner. Light is given
+    /// off on the side `edge1 x edge2` points to.
+    Rectangle {
+        corner: Vector3,
+        edge1: Vector3,
+        edge2: Vector3,
+    },
+    /// A disk giving off light on the side its normal points to
+    Disk {
+        center: Vector3,
+        normal: Vector3,
+        radius: f64,
+    },
+    /// A sphere giving off light in all directions
+    Sphere { center: Vector3, radius: f64 },
+}
+
+#[derive(Debug)]
+pub struct AreaLight {
+    pub color: Pixel,
+    pub shape: LightShape,
+    /// Shadow rays per intersection
+    pub samples: usize,
+}
+
+impl AreaLight {
+    pub fn new(color: Pixel, shape: LightShape, samples: usize) -> Self {
+        Self {
+            color,
+            shape,
+            samples: samples.max(1),
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        random: (f64, f64),
+    ) -> LightSample {
+        let (u, v) = random;
+        let (position, normal, weight) = match self.shape {
+            LightShape::Rectangle {
+                corner,
+                edge1,
+                edge2,
+            } => (
+                corner + edge1 * u + edge2 * v,
+                edge1.cross(&edge2).normalized(),
+                1.0,
+            ),
+            LightShape::Disk {
+                center,
+                normal,
+                radius,
+            } => {
+                let normal = normal.normalized();
+                let (tangent, bitangent) = tangents(normal);
+                let (x, y) = concentric_disk(u, v);
+                (center + (tangent * x + bitangent * y) * radius, normal, 1.0)
+            }
+            LightShape::Sphere { center, radius } => {
+                // Only the half of the sphere facing the intersection can
+                // light it. Averaged over that hemisphere, twice the cosine
+                // is 1, like a point light.
+                let axis = (intersection.point - center).normalized();
+                let (tangent, bitangent) = tangents(axis);
+                let z = u;
+                let r = (1.0 - z * z).max(0.0).sqrt();
+                let phi = 2.0 * PI * v;
+                let normal = tangent * (r * phi.cos())
+                    + bitangent * (r * phi.sin())
+                    + axis * z;
+                (center + normal * radius, normal, 2.0)
+            }
+        };
+        let mut sample = LightSample::towards(intersection, position);
+        // Seen at a grazing angle, a surface gives off less light
+        sample.weight = weight * normal.dot(&-sample.direction).max(0.0);
+        sample
+    }
+
+    fn diffuse(
+        &self,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        let source_illumination = sample.weight / (sample.distance.powf(2.0));
+        let angle = intersection.surface_normal.dot(&sample.direction).max(0.0);
+        self.color * material.diffuse * angle * source_illumination
+    }
+
+    fn specular(
+        &self,
+        camera: &Camera,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        let view = (camera.position - intersection.point).normalized();
+        let reflection = sample.direction.reflect(&intersection.surface_normal);
+        let phong_dot =
+            view.dot(&reflection).min(0.0).powf(material.phong_power);
+        self.color.clamp() * material.specular * phong_dot * sample.weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn far_away_looks_like_a_point_light() {
+        let intersection =
+            Intersection::new(Vector3::new(0.0, 1.0, 0.0), Vector3::default());
+        let position = Vector3::new(0.0, 100.0, 0.0);
+        let shapes = [
+            LightShape::Rectangle {
+                corner: position - Vector3::new(0.5, 0.0, 0.5),
+                // Facing down, towards the intersection
+                edge1: Vector3::new(1.0, 0.0, 0.0),
+                edge2: Vector3::new(0.0, 0.0, 1.0),
+            },
+            LightShape::Disk {
+                center: position,
+                normal: Vector3::new(0.0, -1.0, 0.0),
+                radius: 0.5,
+            },
+            LightShape::Sphere {
+                center: position,
+                radius: 0.5,
+            },
+        ];
+        for &shape in &shapes {
+            let light =
+                AreaLight::new(Pixel::from_rgb(1.0, 1.0, 1.0), shape, 1);
+            // Average the light's weight over a grid of samples
+            let count = 32;
+            let mut total = 0.0;
+            for i in 0..count {
+                for j in 0..count {
+                    let u = (i as f64 + 0.5) / count as f64;
+                    let v = (j as f64 + 0.5) / count as f64;
+                    total += light.sample(&intersection, (u, v)).weight;
+                }
+            }
+            let average = total / (count * count) as f64;
+            assert!((average - 1.0).abs() < 0.01, "{:?}: {}", shape, average);
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::camera::Camera;
 use crate::intersection::Intersection;
-use crate::lights::light::Light;
+use crate::lights::light::{Light, LightSample};
 use crate::material::Material;
 use crate::pixel::Pixel;
 use crate::vector::Vector3;
@@ -20,23 +20,25 @@ impl DirectionalLight {
 }
 
 impl Light for DirectionalLight {
-    fn direction_to_light(&self, _intersection: &Intersection) -> Vector3 {
-        -(self.direction.normalized())
-    }
-
-    fn distance_to_light(&self, _intersection: &Intersection) -> f64 {
-        ::std::f64::MAX
+    fn sample(
+        &self,
+        _intersection: &Intersection,
+        _random: (f64, f64),
+    ) -> LightSample {
+        LightSample {
+            direction: -(self.direction.normalized()),
+            distance: ::std::f64::MAX,
+            weight: 1.0,
+        }
     }
 
     fn diffuse(
         &self,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel {
-        let angle = intersection
-            .surface_normal
-            .dot(&self.direction_to_light(intersection))
-            .max(0.0);
+        let angle = intersection.surface_normal.dot(&sample.direction).max(0.0);
         self.color * material.diffuse * angle
     }
 
@@ -45,11 +47,10 @@ impl Light for DirectionalLight {
         camera: &Camera,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel {
         let view = (camera.position - intersection.point).normalized();
-        let reflection = self
-            .direction_to_light(intersection)
-            .reflect(&intersection.surface_normal);
+        let reflection = sample.direction.reflect(&intersection.surface_normal);
         let phong_dot =
             view.dot(&reflection).min(0.0).powf(material.phong_power);
         self.color.clamp() * material.specular * phong_dot
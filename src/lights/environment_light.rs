@@ -0,0 +1,177 @@
+//! Environment light. The scene's environment map lights it from all
+//! directions, like the sky.
+//!
+//! Directions are picked in proportion to how much light comes from them
+//! (importance sampling), so small bright spots like the sun get most of
+//! the shadow rays and cast sharp shadows.
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::environment::Environment;
+use crate::intersection::Intersection;
+use crate::lights::light::{Light, LightSample};
+use crate::material::Material;
+use crate::pixel::Pixel;
+
+#[derive(Debug)]
+pub struct EnvironmentLight {
+    pub environment: Arc<Environment>,
+    /// Shadow rays per intersection
+    pub samples: usize,
+    /// Total sampling weight of the texels up to and including each one,
+    /// row by row
+    cumulative_weights: Vec<f64>,
+}
+
+impl EnvironmentLight {
+    pub fn new(environment: Arc<Environment>, samples: usize) -> Self {
+        let (width, height) =
+            (environment.image.width, environment.image.height);
+        let mut total = 0.0;
+        let mut cumulative_weights = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for col in 0..width {
+                let (x, y) = (col as f64 + 0.5, row as f64 + 0.5);
+                if environment.direction_at(x, y).is_some() {
+                    let texel = environment
+                        .image
+                        .get_pixel(row, col)
+                        .map(|texel| texel.luminance())
+                        .unwrap_or(0.0);
+                    total += texel * environment.texel_solid_angle(x, y);
+                }
+                cumulative_weights.push(total);
+            }
+        }
+        Self {
+            environment,
+            samples: samples.max(1),
+            cumulative_weights,
+        }
+    }
+}
+
+impl Light for EnvironmentLight {
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        random: (f64, f64),
+    ) -> LightSample {
+        let total = self.cumulative_weights.last().cloned().unwrap_or(0.0);
+        if total <= 0.0 {
+            // A black environment gives off no light
+            return LightSample {
+                direction: intersection.surface_normal,
+                distance: f64::INFINITY,
+                weight: 0.0,
+            };
+        }
+
+        // Pick a texel in proportion to its weight, then reuse what's left
+        // of the first number to pick a point in it
+        let target = random.0 * total;
+        let index = self
+            .cumulative_weights
+            .partition_point(|&weight| weight <= target)
+            .min(self.cumulative_weights.len() - 1);
+        let start = if index == 0 {
+            0.0
+        } else {
+            self.cumulative_weights[index - 1]
+        };
+        let texel_weight = self.cumulative_weights[index] - start;
+        let fraction = ((target - start) / texel_weight).min(1.0 - 1e-9);
+        let width = self.environment.image.width;
+        let x = (index % width) as f64 + fraction;
+        let y = (index / width) as f64 + random.1;
+
+        let direction = self
+            .environment
+            .direction_at(x, y)
+            .unwrap_or(intersection.surface_normal);
+        // Probability density of this direction, per unit solid angle
+        let density = texel_weight
+            / total
+            / self.environment.texel_solid_angle(x, y).max(1e-12);
+        LightSample {
+            direction,
+            distance: f64::INFINITY,
+            // A diffuse surface reflects 1 / π of the light it receives
+            // per unit solid angle
+            weight: 1.0 / (PI * density),
+        }
+    }
+
+    fn diffuse(
+        &self,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        let angle = intersection.surface_normal.dot(&sample.direction).max(0.0);
+        self.environment.color(sample.direction)
+            * material.diffuse
+            * angle
+            * sample.weight
+    }
+
+    /// Reflections of the environment come from mirror rays instead
+    fn specular(
+        &self,
+        _camera: &Camera,
+        _intersection: &Intersection,
+        _material: &Material,
+        _sample: &LightSample,
+    ) -> Pixel {
+        Pixel::from_rgb(0.0, 0.0, 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::EnvironmentLayout;
+    use crate::image::Image;
+    use crate::random::Rng;
+    use crate::sampler;
+    use crate::vector::Vector3;
+
+    #[test]
+    fn uniform_sky_lights_like_a_diffuse_bounce() {
+        let mut image = Image::new(16, 8);
+        for row in 0..8 {
+            for col in 0..16 {
+                image.set_pixel(row, col, Pixel::from_rgb(0.5, 0.5, 0.5));
+            }
+        }
+        let environment = Environment::new(
+            Arc::new(image),
+            EnvironmentLayout::Equirectangular,
+            0.0,
+            1.0,
+        )
+        .unwrap();
+        let light = EnvironmentLight::new(Arc::new(environment), 1);
+        let intersection = Intersection::new(
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+        );
+        let material = Material::default();
+
+        let points = sampler::stratified(1024, &mut Rng::new(5));
+        let mut mean = 0.0;
+        for &point in &points {
+            let sample = light.sample(&intersection, point);
+            mean += light.diffuse(&intersection, &material, &sample).g
+                / points.len() as f64;
+        }
+        // A white diffuse surface under a uniform sky reflects all of it
+        assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+    }
+}
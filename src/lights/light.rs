@@ -1,6 +1,8 @@
 //! A generic light.
 //!
 //! Calculates the diffuse and specular components for a point on a surface.
+//! Lights are sampled: a light with an area is seen as many points, each
+//! tested for shadows and shaded on its own.
 
 use std::fmt;
 
@@ -10,18 +12,55 @@ use crate::material::Material;
 use crate::pixel::Pixel;
 use crate::vector::Vector3;
 
-pub trait Light {
-    /// Calculate the direction of the light from the intersection
-    fn direction_to_light(&self, intersection: &Intersection) -> Vector3;
+/// A point on a light, as seen from an intersection
+#[derive(Debug, Clone, Copy)]
+pub struct LightSample {
+    /// Unit direction from the intersection to the point on the light
+    pub direction: Vector3,
 
-    /// Calculate the distance to the light from the intersection
-    fn distance_to_light(&self, intersection: &Intersection) -> f64;
+    /// Distance from the intersection to the point on the light
+    pub distance: f64,
+
+    /// How much this point contributes, e.g. less when an area light is
+    /// seen at a grazing angle
+    pub weight: f64,
+}
+
+impl LightSample {
+    /// A sample of a point at `position`
+    pub fn towards(intersection: &Intersection, position: Vector3) -> Self {
+        let to_light = position - intersection.point;
+        Self {
+            direction: to_light.normalized(),
+            distance: to_light.length(),
+            weight: 1.0,
+        }
+    }
+}
+
+/// Lights are shared between render threads, so they must be `Send` and
+/// `Sync`
+pub trait Light: Send + Sync {
+    /// How many points to sample on the light per intersection. Their
+    /// contributions are averaged.
+    fn sample_count(&self) -> usize {
+        1
+    }
+
+    /// Pick a point on the light, given two numbers in [0, 1) that choose
+    /// where
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        random: (f64, f64),
+    ) -> LightSample;
 
     /// Calculate the diffuse component
     fn diffuse(
         &self,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel;
 
     /// Calculate the specular component
@@ -30,6 +69,7 @@ pub trait Light {
         camera: &Camera,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel;
 }
 
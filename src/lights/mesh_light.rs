@@ -0,0 +1,173 @@
+//! Mesh light. Glowing triangles light the scene like an area light of the
+//! same shape.
+//!
+//! Stands in for triangles with an emissive material. The color is the
+//! light's total intensity, as for an `AreaLight`, worked out from the
+//! emitted light and the area of the triangles.
+
+use std::f64::consts::PI;
+
+use crate::camera::Camera;
+use crate::intersection::Intersection;
+use crate::lights::light::{Light, LightSample};
+use crate::material::Material;
+use crate::pixel::Pixel;
+use crate::vector::Vector3;
+
+/// Distance from the surface to put sampled points at, so shadow rays
+/// don't hit the glowing triangles themselves
+const SURFACE_OFFSET: f64 = 0.001;
+
+#[derive(Debug)]
+pub struct MeshLight {
+    pub color: Pixel,
+    pub triangles: Vec<[Vector3; 3]>,
+    /// Total area of the triangles up to and including each one
+    cumulative_areas: Vec<f64>,
+    /// Shadow rays per intersection
+    pub samples: usize,
+}
+
+impl MeshLight {
+    /// Triangles giving off `emitted` light from both sides. They must have
+    /// some area.
+    pub fn new(
+        emitted: Pixel,
+        triangles: Vec<[Vector3; 3]>,
+        samples: usize,
+    ) -> Self {
+        let mut total_area = 0.0;
+        let cumulative_areas = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                total_area += (b - a).cross(&(c - a)).length() / 2.0;
+                total_area
+            })
+            .collect();
+        Self {
+            color: Pixel::from_pix_unclamped(emitted) * (total_area / PI),
+            triangles,
+            cumulative_areas,
+            samples: samples.max(1),
+        }
+    }
+}
+
+impl Light for MeshLight {
+    fn sample_count(&self) -> usize {
+        self.samples
+    }
+
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        random: (f64, f64),
+    ) -> LightSample {
+        // Pick a triangle in proportion to its area, then reuse what's left
+        // of `u` to pick a point on it
+        let total_area = self.cumulative_areas.last().cloned().unwrap_or(0.0);
+        let target = random.0 * total_area;
+        let index = self
+            .cumulative_areas
+            .partition_point(|&area| area <= target)
+            .min(self.triangles.len() - 1);
+        let start = if index == 0 {
+            0.0
+        } else {
+            self.cumulative_areas[index - 1]
+        };
+        let area = self.cumulative_areas[index] - start;
+        let u = if area > 0.0 {
+            ((target - start) / area).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let v = random.1;
+
+        let [a, b, c] = self.triangles[index];
+        let root_u = u.sqrt();
+        let position =
+            a * (1.0 - root_u) + b * (root_u * (1.0 - v)) + c * (root_u * v);
+        // Both sides glow, so use the side facing the intersection
+        let mut normal = (b - a).cross(&(c - a)).normalized();
+        if normal.dot(&(intersection.point - position)) < 0.0 {
+            normal = -normal;
+        }
+
+        let mut sample = LightSample::towards(
+            intersection,
+            position + normal * SURFACE_OFFSET,
+        );
+        // Seen at a grazing angle, a surface gives off less light
+        sample.weight = normal.dot(&-sample.direction).max(0.0);
+        sample
+    }
+
+    fn diffuse(
+        &self,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        let source_illumination = sample.weight / (sample.distance.powf(2.0));
+        let angle = intersection.surface_normal.dot(&sample.direction).max(0.0);
+        self.color * material.diffuse * angle * source_illumination
+    }
+
+    fn specular(
+        &self,
+        camera: &Camera,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        let view = (camera.position - intersection.point).normalized();
+        let reflection = sample.direction.reflect(&intersection.surface_normal);
+        let phong_dot =
+            view.dot(&reflection).min(0.0).powf(material.phong_power);
+        self.color.clamp() * material.specular * phong_dot * sample.weight
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_land_on_the_triangles() {
+        let triangles = vec![
+            [
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+            ],
+            // Three times the area, so picked three times as often
+            [
+                Vector3::new(5.0, 0.0, 0.0),
+                Vector3::new(8.0, 0.0, 0.0),
+                Vector3::new(5.0, 1.0, 0.0),
+            ],
+        ];
+        let light =
+            MeshLight::new(Pixel::from_rgb(1.0, 1.0, 1.0), triangles, 1);
+        assert!((light.color.r - 2.0 / PI).abs() < 1e-12);
+
+        let intersection = Intersection::new(
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(2.0, 0.0, -4.0),
+        );
+        let mut on_second = 0;
+        for i in 0..100 {
+            let random = ((i as f64 + 0.5) / 100.0, 0.3);
+            let sample = light.sample(&intersection, random);
+            let point = intersection.point + sample.direction * sample.distance;
+            // Just in front of the triangles, on the intersection's side
+            assert!((point.z + SURFACE_OFFSET).abs() < 1e-9);
+            assert!(point.y >= 0.0 && point.y <= 1.0);
+            if point.x >= 5.0 {
+                on_second += 1;
+            }
+        }
+        assert_eq!(on_second, 75);
+    }
+}
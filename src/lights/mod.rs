@@ -1,5 +1,9 @@
 //! Various lights that can be represented in a ray tracer
+pub mod area_light;
 pub mod directional_light;
+pub mod environment_light;
 pub mod light;
+pub mod mesh_light;
 pub mod point_light;
 pub mod spot_light;
+pub mod transformed_light;
@@ -2,7 +2,7 @@
 
 use crate::camera::Camera;
 use crate::intersection::Intersection;
-use crate::lights::light::Light;
+use crate::lights::light::{Light, LightSample};
 use crate::material::Material;
 use crate::pixel::Pixel;
 use crate::vector::Vector3;
@@ -35,28 +35,23 @@ impl SpotLight {
 }
 
 impl Light for SpotLight {
-    fn direction_to_light(&self, intersection: &Intersection) -> Vector3 {
-        self.position - intersection.point
-    }
-
-    fn distance_to_light(&self, intersection: &Intersection) -> f64 {
-        (self.position - intersection.point).length()
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        _random: (f64, f64),
+    ) -> LightSample {
+        LightSample::towards(intersection, self.position)
     }
 
     fn diffuse(
         &self,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel {
-        let angle_to_light = self
-            .direction_to_light(intersection)
-            .angle(&-self.direction);
-        let source_illumination =
-            1.0 / (self.distance_to_light(intersection).powf(2.0));
-        let angle = intersection
-            .surface_normal
-            .dot(&self.direction_to_light(intersection).normalized())
-            .max(0.0);
+        let angle_to_light = sample.direction.angle(&-self.direction);
+        let source_illumination = 1.0 / (sample.distance.powf(2.0));
+        let angle = intersection.surface_normal.dot(&sample.direction).max(0.0);
         let output =
             self.color * material.diffuse * angle * source_illumination;
         if angle_to_light > self.angle2 {
@@ -75,15 +70,11 @@ impl Light for SpotLight {
         camera: &Camera,
         intersection: &Intersection,
         material: &Material,
+        sample: &LightSample,
     ) -> Pixel {
-        let angle_to_light = self
-            .direction_to_light(intersection)
-            .angle(&-self.direction);
+        let angle_to_light = sample.direction.angle(&-self.direction);
         let view = (camera.position - intersection.point).normalized();
-        let reflection = self
-            .direction_to_light(intersection)
-            .normalized()
-            .reflect(&intersection.surface_normal);
+        let reflection = sample.direction.reflect(&intersection.surface_normal);
         let phong_dot =
             view.dot(&reflection).min(0.0).powf(material.phong_power);
         let output = self.color.clamp() * material.specular * phong_dot;
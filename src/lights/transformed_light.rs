@@ -0,0 +1,110 @@
+//! A light moved, rotated or scaled by a transform, standing in for a
+//! glowing `Instance`
+//!
+//! Points are sampled on the wrapped light in its own space and brought out
+//! to the world. Scaling changes how much area glows, so the light gets
+//! brighter or dimmer to match. For uneven scaling this is only an average,
+//! since the area grows by different amounts in different places.
+
+use crate::camera::Camera;
+use crate::intersection::Intersection;
+use crate::lights::light::{Light, LightSample};
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::pixel::Pixel;
+use crate::vector::Vector3;
+
+#[derive(Debug)]
+pub struct TransformedLight {
+    pub light: Box<Light>,
+    /// From the light's space to the world
+    pub transform: Matrix4,
+    /// From the world to the light's space
+    inverse: Matrix4,
+    /// How much bigger areas are in the world than in the light's space
+    area_scale: f64,
+}
+
+impl TransformedLight {
+    /// `None` if the transform can't be inverted
+    pub fn new(light: Box<Light>, transform: Matrix4) -> Option<Self> {
+        let inverse = transform.inverse()?;
+        let axis =
+            |x, y, z| transform.transform_direction(Vector3::new(x, y, z));
+        let volume_scale = axis(1.0, 0.0, 0.0)
+            .dot(&axis(0.0, 1.0, 0.0).cross(&axis(0.0, 0.0, 1.0)))
+            .abs();
+        Some(Self {
+            light,
+            transform,
+            inverse,
+            area_scale: volume_scale.powf(2.0 / 3.0),
+        })
+    }
+
+    /// Whether the transform keeps shapes the same, only moving, turning,
+    /// mirroring or evenly scaling them, so the brightness is exact
+    pub fn scales_evenly(&self) -> bool {
+        let axis =
+            |x, y, z| self.transform.transform_direction(Vector3::new(x, y, z));
+        let axes = [
+            axis(1.0, 0.0, 0.0),
+            axis(0.0, 1.0, 0.0),
+            axis(0.0, 0.0, 1.0),
+        ];
+        let scale = axes[0].length();
+        let tolerance = 1e-9 * scale * scale;
+        (0..3).all(|i| {
+            (axes[i].dot(&axes[i]) - scale * scale).abs() < tolerance
+                && axes[i].dot(&axes[(i + 1) % 3]).abs() < tolerance
+        })
+    }
+}
+
+impl Light for TransformedLight {
+    fn sample_count(&self) -> usize {
+        self.light.sample_count()
+    }
+
+    fn sample(
+        &self,
+        intersection: &Intersection,
+        random: (f64, f64),
+    ) -> LightSample {
+        let local_intersection = Intersection::new(
+            self.transform
+                .transposed()
+                .transform_direction(intersection.surface_normal)
+                .normalized(),
+            self.inverse.transform_point(intersection.point),
+        );
+        let local = self.light.sample(&local_intersection, random);
+        let position = self.transform.transform_point(
+            local_intersection.point + local.direction * local.distance,
+        );
+        LightSample {
+            weight: local.weight,
+            ..LightSample::towards(intersection, position)
+        }
+    }
+
+    fn diffuse(
+        &self,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        self.light.diffuse(intersection, material, sample) * self.area_scale
+    }
+
+    fn specular(
+        &self,
+        camera: &Camera,
+        intersection: &Intersection,
+        material: &Material,
+        sample: &LightSample,
+    ) -> Pixel {
+        self.light.specular(camera, intersection, material, sample)
+            * self.area_scale
+    }
+}
@@ -1,6 +1,13 @@
 //! A material to apply to a ray-traced object
 
+use std::borrow::Cow;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::fresnel::Fresnel;
+use crate::intersection::Intersection;
 use crate::pixel::Pixel;
+use crate::textures::texture::Texture;
 
 /// Ray tracing material
 ///
@@ -10,6 +17,12 @@ use crate::pixel::Pixel;
 /// - specular
 /// - transmissive
 /// - index of refraction
+/// - absorption
+/// - emission
+///
+/// Each color can be modulated by a texture. With Fresnel enabled, the
+/// transmissive part is split between reflection and refraction depending
+/// on the viewing angle.
 #[derive(Debug, Clone)]
 pub struct Material {
     pub ambient: Pixel,
@@ -18,6 +31,41 @@ pub struct Material {
     pub phong_power: f64,
     pub transmissive: Pixel,
     pub ior: f64,
+    pub fresnel: Fresnel,
+    /// Fraction of each color absorbed per unit of distance travelled
+    /// inside the object (Beer–Lambert law)
+    pub absorption: Pixel,
+    /// Color of the light the surface gives off, scaled by
+    /// `emission_strength`
+    pub emission: Pixel,
+    pub emission_strength: f64,
+    pub ambient_texture: Option<Arc<Texture>>,
+    pub diffuse_texture: Option<Arc<Texture>>,
+    pub specular_texture: Option<Arc<Texture>>,
+    pub transmissive_texture: Option<Arc<Texture>>,
+}
+
+/// The colors of a material that a texture can be applied to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChannel {
+    Ambient,
+    Diffuse,
+    Specular,
+    Transmissive,
+}
+
+impl FromStr for ColorChannel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ambient" => Ok(ColorChannel::Ambient),
+            "diffuse" => Ok(ColorChannel::Diffuse),
+            "specular" => Ok(ColorChannel::Specular),
+            "transmissive" => Ok(ColorChannel::Transmissive),
+            _ => Err(()),
+        }
+    }
 }
 
 impl Default for Material {
@@ -29,6 +77,14 @@ impl Default for Material {
             phong_power: 5.0,
             transmissive: Pixel::from_rgb(0.0, 0.0, 0.0),
             ior: 1.0,
+            fresnel: Fresnel::Off,
+            absorption: Pixel::from_rgb(0.0, 0.0, 0.0),
+            emission: Pixel::from_rgb(0.0, 0.0, 0.0),
+            emission_strength: 1.0,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            transmissive_texture: None,
         }
     }
 }
@@ -49,6 +105,92 @@ impl Material {
             phong_power,
             transmissive,
             ior,
+            fresnel: Fresnel::Off,
+            absorption: Pixel::from_rgb(0.0, 0.0, 0.0),
+            emission: Pixel::from_rgb(0.0, 0.0, 0.0),
+            emission_strength: 1.0,
+            ambient_texture: None,
+            diffuse_texture: None,
+            specular_texture: None,
+            transmissive_texture: None,
+        }
+    }
+
+    /// How much of each color is left after travelling `distance` inside
+    /// the object
+    pub fn transmittance(&self, distance: f64) -> Pixel {
+        Pixel::from_rgb(
+            (-self.absorption.r * distance).exp(),
+            (-self.absorption.g * distance).exp(),
+            (-self.absorption.b * distance).exp(),
+        )
+    }
+
+    /// Light given off by the surface, in every direction
+    pub fn emitted(&self) -> Pixel {
+        Pixel::from_pix_unclamped(self.emission) * self.emission_strength
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        let emitted = self.emitted();
+        emitted.r > 0.0 || emitted.g > 0.0 || emitted.b > 0.0
+    }
+
+    /// Modulate one of the colors with a texture
+    pub fn set_texture(
+        &mut self,
+        channel: ColorChannel,
+        texture: Arc<Texture>,
+    ) {
+        let slot = match channel {
+            ColorChannel::Ambient => &mut self.ambient_texture,
+            ColorChannel::Diffuse => &mut self.diffuse_texture,
+            ColorChannel::Specular => &mut self.specular_texture,
+            ColorChannel::Transmissive => &mut self.transmissive_texture,
+        };
+        *slot = Some(texture);
+    }
+
+    /// The material at a point on a surface, with any textures looked up
+    pub fn at(&self, intersection: &Intersection) -> Cow<'_, Material> {
+        if self.ambient_texture.is_none()
+            && self.diffuse_texture.is_none()
+            && self.specular_texture.is_none()
+            && self.transmissive_texture.is_none()
+        {
+            return Cow::Borrowed(self);
         }
+        let mut material = self.clone();
+        let channels = [
+            (&mut material.ambient, &self.ambient_texture),
+            (&mut material.diffuse, &self.diffuse_texture),
+            (&mut material.specular, &self.specular_texture),
+            (&mut material.transmissive, &self.transmissive_texture),
+        ];
+        for (color, texture) in channels {
+            if let Some(texture) = texture {
+                *color = *color * texture.color_at(intersection);
+            }
+        }
+        Cow::Owned(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transmittance_falls_off_exponentially() {
+        let mut material = Material::default();
+        assert_eq!(material.transmittance(10.0).r, 1.0);
+
+        material.absorption = Pixel::from_rgb(0.0, 0.5, 2.0);
+        let near = material.transmittance(1.0);
+        let far = material.transmittance(2.0);
+        assert_eq!(near.r, 1.0);
+        assert!((near.g - (-0.5f64).exp()).abs() < 1e-12);
+        assert!((far.g - near.g * near.g).abs() < 1e-12);
+        assert!(far.b < near.b);
     }
 }
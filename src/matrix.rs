@@ -0,0 +1,216 @@
+//! A 4x4 matrix for affine transforms
+
+use std::ops::Mul;
+
+use crate::vector::Vector3;
+
+/// 4x4 matrix, stored row by row. Points are column vectors, so a transform
+/// `a * b` applies `b` first.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Matrix4 {
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul<Matrix4> for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (row, result) in rows.iter_mut().enumerate() {
+            for (col, value) in result.iter_mut().enumerate() {
+                *value = (0..4)
+                    .map(|i| self.rows[row][i] * other.rows[i][col])
+                    .sum();
+            }
+        }
+        Self { rows }
+    }
+}
+
+impl Matrix4 {
+    pub fn identity() -> Self {
+        Self {
+            rows: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// An affine transform from the top three rows; the bottom row is
+    /// always 0 0 0 1
+    pub fn from_rows(rows: [[f64; 4]; 3]) -> Self {
+        Self {
+            rows: [rows[0], rows[1], rows[2], [0.0, 0.0, 0.0, 1.0]],
+        }
+    }
+
+    pub fn translation(offset: Vector3) -> Self {
+        Self::from_rows([
+            [1.0, 0.0, 0.0, offset.x],
+            [0.0, 1.0, 0.0, offset.y],
+            [0.0, 0.0, 1.0, offset.z],
+        ])
+    }
+
+    pub fn scaling(factors: Vector3) -> Self {
+        Self::from_rows([
+            [factors.x, 0.0, 0.0, 0.0],
+            [0.0, factors.y, 0.0, 0.0],
+            [0.0, 0.0, factors.z, 0.0],
+        ])
+    }
+
+    /// Rotation by `angle` radians about `axis`, counterclockwise looking
+    /// down the axis towards the origin
+    pub fn rotation(axis: Vector3, angle: f64) -> Self {
+        let Vector3 { x, y, z } = axis.normalized();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        Self::from_rows([
+            [
+                t * x * x + cos,
+                t * x * y - sin * z,
+                t * x * z + sin * y,
+                0.0,
+            ],
+            [
+                t * x * y + sin * z,
+                t * y * y + cos,
+                t * y * z - sin * x,
+                0.0,
+            ],
+            [
+                t * x * z - sin * y,
+                t * y * z + sin * x,
+                t * z * z + cos,
+                0.0,
+            ],
+        ])
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::identity()
+    }
+
+    pub fn transposed(&self) -> Self {
+        let mut rows = [[0.0; 4]; 4];
+        for (row, result) in rows.iter_mut().enumerate() {
+            for (col, value) in result.iter_mut().enumerate() {
+                *value = self.rows[col][row];
+            }
+        }
+        Self { rows }
+    }
+
+    /// The inverse, or `None` if the matrix squashes space flat (e.g. a
+    /// scale of 0)
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut left = self.rows;
+        let mut right = Self::identity().rows;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&a, &b| {
+                    left[a][col]
+                        .abs()
+                        .partial_cmp(&left[b][col].abs())
+                        .unwrap_or(::std::cmp::Ordering::Equal)
+                })
+                .unwrap_or(col);
+            if left[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            left.swap(col, pivot);
+            right.swap(col, pivot);
+
+            let scale = 1.0 / left[col][col];
+            for i in 0..4 {
+                left[col][i] *= scale;
+                right[col][i] *= scale;
+            }
+            for row in 0..4 {
+                let factor = left[row][col];
+                if row == col || factor == 0.0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    left[row][i] -= factor * left[col][i];
+                    right[row][i] -= factor * right[col][i];
+                }
+            }
+        }
+        Some(Self { rows: right })
+    }
+
+    /// Transform a position, including translation
+    pub fn transform_point(&self, point: Vector3) -> Vector3 {
+        let [x, y, z] = self.apply(point, 1.0);
+        Vector3::new(x, y, z)
+    }
+
+    /// Transform a direction, ignoring translation. Normals need the
+    /// inverse transpose instead.
+    pub fn transform_direction(&self, direction: Vector3) -> Vector3 {
+        let [x, y, z] = self.apply(direction, 0.0);
+        Vector3::new(x, y, z)
+    }
+
+    fn apply(&self, vector: Vector3, w: f64) -> [f64; 3] {
+        let mut result = [0.0; 3];
+        for (row, value) in result.iter_mut().enumerate() {
+            let r = &self.rows[row];
+            *value =
+                r[0] * vector.x + r[1] * vector.y + r[2] * vector.z + r[3] * w;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vector3, b: Vector3) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn transforms_compose_and_invert() {
+        let transform = Matrix4::translation(Vector3::new(1.0, 2.0, 3.0))
+            * Matrix4::rotation(
+                Vector3::new(0.0, 1.0, 0.0),
+                90_f64.to_radians(),
+            )
+            * Matrix4::scaling(Vector3::new(2.0, 2.0, 2.0));
+        let point = Vector3::new(1.0, 0.0, 0.0);
+        // Scaled to (2, 0, 0), turned to (0, 0, -2), then moved
+        let moved = transform.transform_point(point);
+        assert_close(moved, Vector3::new(1.0, 2.0, 1.0));
+        assert_close(
+            transform.transform_direction(point),
+            Vector3::new(0.0, 0.0, -2.0),
+        );
+
+        let inverse = transform.inverse().unwrap();
+        assert_close(inverse.transform_point(moved), point);
+        let product = transform * inverse;
+        for row in 0..4 {
+            for col in 0..4 {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((product.rows[row][col] - expected).abs() < 1e-9);
+            }
+        }
+
+        let flat = Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0));
+        assert!(flat.inverse().is_none());
+    }
+}
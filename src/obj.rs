@@ -0,0 +1,221 @@
+//! Wavefront OBJ mesh loading
+//!
+//! Supports vertex positions (`v`), normals (`vn`), texture coordinates
+//! (`vt`) and polygonal faces (`f`) in all the usual `v`, `v/vt`, `v//vn`
+//! and `v/vt/vn` forms, with positive or negative (relative) indices.
+//! Polygons are split into triangle fans. Everything else (groups,
+//! materials, smoothing groups...) is ignored.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::vector::Vector3;
+
+/// One corner of a face, as 0-based indices into the mesh's lists
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjVertex {
+    pub position: usize,
+    pub texcoord: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct ObjMesh {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub texcoords: Vec<(f64, f64)>,
+    /// Faces, already split into triangles
+    pub triangles: Vec<[ObjVertex; 3]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjError {
+    /// Line in the OBJ file (1-based)
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ObjError {}
+
+impl ObjMesh {
+    pub fn parse(text: &str) -> Result<Self, ObjError> {
+        let mut mesh = Self::default();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let error = |message: String| ObjError {
+                line: line_index + 1,
+                message,
+            };
+            let line = match line.find('#') {
+                Some(index) => &line[..index],
+                None => line,
+            };
+            let tokens: Vec<_> = line.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+
+            match tokens[0] {
+                "v" => {
+                    // An optional fourth (w) component is ignored
+                    let floats =
+                        parse_floats(&tokens[1..], 3, 4).map_err(error)?;
+                    mesh.positions.push(Vector3::from(&floats[..3]));
+                }
+                "vn" => {
+                    let floats =
+                        parse_floats(&tokens[1..], 3, 3).map_err(error)?;
+                    mesh.normals.push(Vector3::from(&floats[..]).normalized());
+                }
+                "vt" => {
+                    // v defaults to 0 and an optional w is ignored
+                    let floats =
+                        parse_floats(&tokens[1..], 1, 3).map_err(error)?;
+                    let v = floats.get(1).cloned().unwrap_or(0.0);
+                    mesh.texcoords.push((floats[0], v));
+                }
+                "f" => {
+                    if tokens.len() < 4 {
+                        return Err(error(format!(
+                            "face needs at least 3 vertices, found {}",
+                            tokens.len() - 1
+                        )));
+                    }
+                    let corners = tokens[1..]
+                        .iter()
+                        .map(|token| mesh.parse_vertex(token))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(error)?;
+                    // Triangle fan around the first corner
+                    for i in 1..corners.len() - 1 {
+                        mesh.triangles.push([
+                            corners[0],
+                            corners[i],
+                            corners[i + 1],
+                        ]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    /// Parse a face corner like `3`, `3/1`, `3//2` or `3/1/2`
+    fn parse_vertex(&self, token: &str) -> Result<ObjVertex, String> {
+        let mut parts = token.split('/');
+        let position = resolve_index(
+            parts.next().unwrap_or(""),
+            self.positions.len(),
+            "vertex",
+        )?;
+        let texcoord = match parts.next() {
+            Some("") | None => None,
+            Some(index) => Some(resolve_index(
+                index,
+                self.texcoords.len(),
+                "texture coordinate",
+            )?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(index) => {
+                Some(resolve_index(index, self.normals.len(), "normal")?)
+            }
+        };
+        if parts.next().is_some() {
+            return Err(format!("malformed face vertex `{}`", token));
+        }
+        Ok(ObjVertex {
+            position,
+            texcoord,
+            normal,
+        })
+    }
+}
+
+/// Turn a 1-based (or negative, relative to the end) OBJ index into a
+/// 0-based one
+fn resolve_index(
+    token: &str,
+    count: usize,
+    what: &str,
+) -> Result<usize, String> {
+    let index = token
+        .parse::<i64>()
+        .map_err(|_| format!("invalid {} index `{}`", what, token))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        Err(format!(
+            "{} index {} is out of range ({} defined so far)",
+            what, index, count
+        ))
+    } else {
+        Ok(resolved as usize)
+    }
+}
+
+fn parse_floats(
+    tokens: &[&str],
+    min: usize,
+    max: usize,
+) -> Result<Vec<f64>, String> {
+    if tokens.len() < min || tokens.len() > max {
+        return Err(format!(
+            "expected {} to {} numbers, found {}",
+            min,
+            max,
+            tokens.len()
+        ));
+    }
+    tokens
+        .iter()
+        .map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| format!("`{}` is not a number", token))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quad_with_relative_indices() {
+        let mesh = ObjMesh::parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+             vn 0 0 2\n\
+             f -4/-4/-1 -3/-3/-1 -2/-2/-1 -1/-1/-1\n",
+        )
+        .unwrap();
+        assert_eq!(mesh.triangles.len(), 2);
+        assert_eq!(mesh.normals[0], Vector3::new(0.0, 0.0, 1.0));
+        let second = mesh.triangles[1];
+        assert_eq!(
+            [second[0].position, second[1].position, second[2].position],
+            [0, 2, 3]
+        );
+        assert_eq!(second[2].texcoord, Some(3));
+        assert_eq!(second[2].normal, Some(0));
+    }
+
+    #[test]
+    fn out_of_range_index() {
+        let err = ObjMesh::parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!(err.line, 3);
+    }
+}
@@ -0,0 +1,226 @@
+//! A cone between two end points, with its own radius at each end
+//!
+//! Equal radii make a cylinder, and a radius of 0 comes to a point. The
+//! ends can be left open or capped with disks.
+
+use std::f64::consts::PI;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::objects::disk::{disk_bounds, polar_uv, ring_distance};
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::sampler::tangents;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+#[derive(Debug)]
+pub struct Cone {
+    pub base: Vector3,
+    pub top: Vector3,
+    pub base_radius: f64,
+    pub top_radius: f64,
+    /// Whether the ends are closed off with disks
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Cone {
+    pub fn new(
+        base: Vector3,
+        top: Vector3,
+        base_radius: f64,
+        top_radius: f64,
+        material: Material,
+    ) -> Self {
+        Self {
+            base,
+            top,
+            base_radius,
+            top_radius,
+            capped: false,
+            material,
+        }
+    }
+
+    /// A cone with the same radius at both ends
+    pub fn cylinder(
+        base: Vector3,
+        top: Vector3,
+        radius: f64,
+        material: Material,
+    ) -> Self {
+        Self::new(base, top, radius, radius, material)
+    }
+
+    pub fn with_caps(mut self, capped: bool) -> Self {
+        self.capped = capped;
+        self
+    }
+
+    /// Unit vector from the base to the top, and the distance between them
+    fn axis(&self) -> (Vector3, f64) {
+        let axis = self.top - self.base;
+        (axis.normalized(), axis.length())
+    }
+
+    /// Where the ray crosses the curved side between the ends, if it does
+    fn side_distance(&self, ray: &Ray) -> Option<f64> {
+        let (axis, height) = self.axis();
+        // How fast the radius changes along the axis
+        let slope = (self.top_radius - self.base_radius) / height;
+
+        // The ray, split into parts along and around the axis
+        let start = ray.start - self.base;
+        let (start_along, direction_along) =
+            (start.dot(&axis), ray.direction.dot(&axis));
+        let start_around = start - axis * start_along;
+        let direction_around = ray.direction - axis * direction_along;
+
+        // Solve |around|^2 = radius(along)^2
+        let radius = self.base_radius + slope * start_along;
+        let a = direction_around.dot(&direction_around)
+            - slope * slope * direction_along * direction_along;
+        let b = 2.0
+            * (start_around.dot(&direction_around)
+                - slope * radius * direction_along);
+        let c = start_around.dot(&start_around) - radius * radius;
+
+        let roots = if a.abs() < 1e-12 {
+            // Only one crossing, e.g. parallel to the slope of a cone
+            if b == 0.0 {
+                return None;
+            }
+            [-c / b, f64::INFINITY]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let (near, far) = (
+                (-b - discriminant.sqrt()) / (2.0 * a),
+                (-b + discriminant.sqrt()) / (2.0 * a),
+            );
+            [near.min(far), near.max(far)]
+        };
+        roots.iter().cloned().find(|&t| {
+            let along = start_along + direction_along * t;
+            t > EPSILON && t.is_finite() && along >= 0.0 && along <= height
+        })
+    }
+}
+
+impl Object for Cone {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let (axis, height) = self.axis();
+        let side = self.side_distance(ray);
+        let (base_cap, top_cap) = if self.capped {
+            (
+                ring_distance(ray, self.base, -axis, 0.0, self.base_radius),
+                ring_distance(ray, self.top, axis, 0.0, self.top_radius),
+            )
+        } else {
+            (None, None)
+        };
+
+        // The closest of the side and the caps
+        let closest = [(side, 0), (base_cap, 1), (top_cap, 2)]
+            .iter()
+            .filter_map(|&(t, part)| t.map(|t| (t, part)))
+            .fold(None, |closest: Option<(f64, usize)>, hit| match closest {
+                Some(closest) if closest.0 <= hit.0 => Some(closest),
+                _ => Some(hit),
+            });
+        let (t, part) = closest?;
+        let point = ray.eval(t)?;
+        let offset = point - self.base;
+
+        let (normal, uv) = match part {
+            0 => {
+                let along = offset.dot(&axis);
+                let around = offset - axis * along;
+                // Tilted back along the axis by the slope of the side
+                let slope = (self.top_radius - self.base_radius) / height;
+                let normal = (around.normalized() - axis * slope).normalized();
+                let (tangent, bitangent) = tangents(axis);
+                let angle = around.dot(&bitangent).atan2(around.dot(&tangent));
+                (normal, (0.5 + angle / (2.0 * PI), along / height))
+            }
+            1 => (-axis, polar_uv(offset, axis, 0.0, self.base_radius)),
+            _ => (axis, polar_uv(point - self.top, axis, 0.0, self.top_radius)),
+        };
+        Some(
+            Intersection::new(normal, point)
+                .with_uv(uv)
+                .with_local_point(offset),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (axis, _) = self.axis();
+        let base = disk_bounds(self.base, axis, self.base_radius);
+        let top = disk_bounds(self.top, axis, self.top_radius);
+        Some(base.union(&top))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "Cone: {:?} {:?} {:?} {:?}",
+            self.base, self.top, self.base_radius, self.top_radius
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_side_and_caps() {
+        // Along z, narrowing from radius 2 to 1
+        let cone = Cone::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 2.0),
+            2.0,
+            1.0,
+            Material::default(),
+        );
+        let across =
+            Ray::new(Vector3::new(-5.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0));
+        let hit = cone.intersects(&across).unwrap();
+        assert!((hit.point.x + 1.5).abs() < 1e-9);
+        // The side leans in by 1 over a height of 2
+        let expected = Vector3::new(-2.0, 0.0, 1.0).normalized();
+        assert!((hit.surface_normal - expected).length() < 1e-9);
+        assert!((hit.uv.1 - 0.5).abs() < 1e-9);
+
+        // Open ends let a ray down the axis straight through
+        let down_axis =
+            Ray::new(Vector3::new(0.5, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(cone.intersects(&down_axis).is_none());
+        let capped = cone.with_caps(true);
+        let hit = capped.intersects(&down_axis).unwrap();
+        assert!(hit.point.z.abs() < 1e-9);
+        assert_eq!(hit.surface_normal, Vector3::new(0.0, 0.0, -1.0));
+        assert!((hit.uv.1 - 0.25).abs() < 1e-9);
+
+        // Inside a cylinder, the far side is hit from within
+        let cylinder = Cone::cylinder(
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            1.0,
+            Material::default(),
+        );
+        let inside =
+            Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = cylinder.intersects(&inside).unwrap();
+        assert!((hit.point.z - 1.0).abs() < 1e-9);
+        assert!((hit.surface_normal.z - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,344 @@
+//! Constructive solid geometry: two solids combined into one by union,
+//! intersection or difference
+//!
+//! Combining needs more than the closest hit, so solids also report every
+//! stretch of a ray that lies inside them (see `Object::spans`). The
+//! stretches of the two sides are merged, and the surface of the result is
+//! wherever the ray crosses into or out of the combination.
+
+use std::str::FromStr;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Where a ray crosses the surface of a solid
+#[derive(Debug)]
+pub struct Crossing {
+    /// Distance along the ray
+    pub t: f64,
+    /// Whether the ray goes into the solid here, rather than out of it
+    pub entering: bool,
+    pub intersection: Intersection,
+}
+
+impl Crossing {
+    /// A crossing found along `ray`, going in where the surface faces the
+    /// ray and out where it faces away. Smooth shading is ignored.
+    pub fn new(ray: &Ray, intersection: Intersection) -> Self {
+        Self {
+            t: (intersection.point - ray.start).dot(&ray.direction),
+            entering: intersection.face_normal.dot(&ray.direction) < 0.0,
+            intersection,
+        }
+    }
+}
+
+/// A stretch of a ray inside a solid
+#[derive(Debug)]
+pub struct Span {
+    /// `None` when the ray starts inside
+    pub enter: Option<Crossing>,
+    /// `None` when the ray never comes out
+    pub exit: Option<Crossing>,
+}
+
+impl Span {
+    /// Pair up crossings, in order along the ray, into spans. Crossings that
+    /// don't fit (e.g. going in twice after grazing an edge) are dropped.
+    pub fn from_crossings(crossings: Vec<Crossing>) -> Vec<Self> {
+        let starts_inside =
+            matches!(crossings.first(), Some(crossing) if !crossing.entering);
+        let mut open = if starts_inside {
+            Some(Self {
+                enter: None,
+                exit: None,
+            })
+        } else {
+            None
+        };
+        let mut spans = Vec::new();
+        for crossing in crossings {
+            match (crossing.entering, open.take()) {
+                (true, None) => {
+                    open = Some(Self {
+                        enter: Some(crossing),
+                        exit: None,
+                    })
+                }
+                (false, Some(mut span)) => {
+                    span.exit = Some(crossing);
+                    spans.push(span);
+                }
+                (_, span) => open = span,
+            }
+        }
+        spans.extend(open);
+        spans
+    }
+}
+
+/// How the two solids are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    /// Inside either
+    Union,
+    /// Inside both
+    Intersection,
+    /// Inside the first but not the second
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+impl FromStr for CsgOperation {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(CsgOperation::Union),
+            "intersection" => Ok(CsgOperation::Intersection),
+            "difference" => Ok(CsgOperation::Difference),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Two closed objects combined into one. The whole combination has one
+/// material, rather than those of its parts.
+#[derive(Debug)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<Object>,
+    pub right: Box<Object>,
+    pub material: Material,
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        left: Box<Object>,
+        right: Box<Object>,
+        material: Material,
+    ) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+            material,
+        }
+    }
+
+    /// Sweep along the ray through the crossings of both sides, keeping
+    /// those where the ray goes into or out of the combination
+    fn combine(&self, left: Vec<Span>, right: Vec<Span>) -> Vec<Span> {
+        let starts_inside = |spans: &[Span]| {
+            matches!(spans.first(), Some(Span { enter: None, .. }))
+        };
+        let mut inside = [starts_inside(&left), starts_inside(&right)];
+
+        let mut crossings: Vec<(Crossing, usize)> = Vec::new();
+        for (side, spans) in vec![left, right].into_iter().enumerate() {
+            for span in spans {
+                crossings.extend(span.enter.map(|crossing| (crossing, side)));
+                crossings.extend(span.exit.map(|crossing| (crossing, side)));
+            }
+        }
+        crossings.sort_by(|a, b| {
+            a.0.t
+                .partial_cmp(&b.0.t)
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+
+        let mut was_inside = self.operation.contains(inside[0], inside[1]);
+        let mut combined = Vec::new();
+        for (mut crossing, side) in crossings {
+            inside[side] = crossing.entering;
+            let now_inside = self.operation.contains(inside[0], inside[1]);
+            if now_inside == was_inside {
+                continue;
+            }
+            was_inside = now_inside;
+            // The inside of the second solid is the outside of a difference
+            if side == 1 && self.operation == CsgOperation::Difference {
+                let hit = &mut crossing.intersection;
+                hit.surface_normal = -hit.surface_normal;
+                hit.face_normal = -hit.face_normal;
+            }
+            crossing.entering = now_inside;
+            combined.push(crossing);
+        }
+        Span::from_crossings(combined)
+    }
+}
+
+impl Object for Csg {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        // The first crossing of any kind; if the ray starts inside, that's
+        // where it comes out
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| span.enter.into_iter().chain(span.exit))
+            .next()
+            .map(|crossing| crossing.intersection)
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        self.combine(self.left.spans(ray), self.right.spans(ray))
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let (left, right) =
+            (self.left.bounding_box(), self.right.bounding_box());
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(left), Some(right)) => Some(BoundingBox::new(
+                    Vector3::new(
+                        left.min.x.max(right.min.x),
+                        left.min.y.max(right.min.y),
+                        left.min.z.max(right.min.z),
+                    ),
+                    Vector3::new(
+                        left.max.x.min(right.max.x),
+                        left.max.y.min(right.max.y),
+                        left.max.z.min(right.max.z),
+                    ),
+                )),
+                (bounds, None) | (None, bounds) => bounds,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "Csg: {:?} of {} and {}",
+            self.operation,
+            self.left.info(),
+            self.right.info()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::triangle::Triangle;
+
+    fn sphere(x: f64, radius: f64) -> Box<Object> {
+        Box::new(Sphere::new(
+            radius,
+            Vector3::new(x, 0.0, 0.0),
+            Material::default(),
+        ))
+    }
+
+    #[test]
+    fn combines_overlapping_spheres() {
+        // Two unit spheres overlapping between x = -0.5 and 0.5
+        let combine = |operation| {
+            Csg::new(
+                operation,
+                sphere(-0.5, 1.0),
+                sphere(0.5, 1.0),
+                Material::default(),
+            )
+        };
+        let along_x =
+            Ray::new(Vector3::new(-5.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let ends = |csg: &Csg| -> Vec<(f64, f64)> {
+            csg.spans(&along_x)
+                .iter()
+                .map(|span| {
+                    (
+                        span.enter.as_ref().unwrap().intersection.point.x,
+                        span.exit.as_ref().unwrap().intersection.point.x,
+                    )
+                })
+                .collect()
+        };
+        let close = |spans: Vec<(f64, f64)>, expected: &[(f64, f64)]| {
+            assert_eq!(spans.len(), expected.len(), "{:?}", spans);
+            for (span, expected) in spans.iter().zip(expected) {
+                assert!((span.0 - expected.0).abs() < 1e-9, "{:?}", spans);
+                assert!((span.1 - expected.1).abs() < 1e-9, "{:?}", spans);
+            }
+        };
+        close(ends(&combine(CsgOperation::Union)), &[(-1.5, 1.5)]);
+        close(ends(&combine(CsgOperation::Intersection)), &[(-0.5, 0.5)]);
+        let difference = combine(CsgOperation::Difference);
+        close(ends(&difference), &[(-1.5, -0.5)]);
+
+        // Looking back into the bite taken out, the surface faces the ray
+        let back =
+            Ray::new(Vector3::new(5.0, 0.0, 0.0), Vector3::new(-1.0, 0.0, 0.0));
+        let hit = difference.intersects(&back).unwrap();
+        assert!((hit.point.x + 0.5).abs() < 1e-9);
+        assert!((hit.surface_normal.x - 1.0).abs() < 1e-9);
+
+        // Nested, and starting inside: a shell with a hole drilled through
+        let shell = Csg::new(
+            CsgOperation::Difference,
+            Box::new(Csg::new(
+                CsgOperation::Difference,
+                sphere(0.0, 2.0),
+                sphere(0.0, 1.0),
+                Material::default(),
+            )),
+            Box::new(Sphere::new(
+                0.6,
+                Vector3::new(0.0, 1.5, 0.0),
+                Material::default(),
+            )),
+            Material::default(),
+        );
+        let from_middle =
+            Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        let hit = shell.intersects(&from_middle).unwrap();
+        assert!((hit.point.x - 1.0).abs() < 1e-9);
+        assert!((hit.surface_normal.x + 1.0).abs() < 1e-9);
+        let up_the_hole =
+            Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0));
+        assert!(shell.intersects(&up_the_hole).is_none());
+    }
+
+    #[test]
+    fn smooth_shading_doesnt_flip_crossings() {
+        // Facing up, but shaded as if tipped far over towards +x
+        let tipped = Vector3::new(1.0, 0.0, 0.1).normalized();
+        let triangle = Triangle::new(
+            Material::default(),
+            Vector3::new(-1.0, -1.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            tipped,
+            tipped,
+            tipped,
+        );
+        // Coming down onto the triangle at a grazing angle
+        let ray = Ray::new(
+            Vector3::new(-1.0, 0.0, 0.1),
+            Vector3::new(1.0, 0.0, -0.1),
+        );
+        let hit = triangle.intersects(&ray).unwrap();
+        assert!(hit.surface_normal.dot(&ray.direction) > 0.0);
+        assert!(Crossing::new(&ray, hit).entering);
+    }
+}
@@ -0,0 +1,190 @@
+//! An axis-aligned box. Put it under a rotation to orient it some other
+//! way.
+
+use std::f64;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::lights::light::Light;
+use crate::lights::mesh_light::MeshLight;
+use crate::material::Material;
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+/// The axes texture coordinates follow across the faces perpendicular to
+/// x, y and z
+const UV_AXES: [(usize, usize); 3] = [(2, 1), (0, 2), (0, 1)];
+
+#[derive(Debug)]
+pub struct Cuboid {
+    pub min: Vector3,
+    pub max: Vector3,
+    pub material: Material,
+}
+
+impl Cuboid {
+    /// The box between two opposite corners, in any order
+    pub fn new(corner1: Vector3, corner2: Vector3, material: Material) -> Self {
+        let bounds = BoundingBox::from_points(&[corner1, corner2]);
+        Self {
+            min: bounds.min,
+            max: bounds.max,
+            material,
+        }
+    }
+
+    /// The corner with coordinates from `max` where `bits` has bit 0 (x),
+    /// 1 (y) or 2 (z) set, and from `min` otherwise
+    fn corner(&self, bits: usize) -> Vector3 {
+        let pick = |axis: usize| {
+            if bits & (1 << axis) == 0 {
+                self.min.axis(axis)
+            } else {
+                self.max.axis(axis)
+            }
+        };
+        Vector3::new(pick(0), pick(1), pick(2))
+    }
+}
+
+impl Object for Cuboid {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        // Slab test, remembering which axis the ray enters and leaves by
+        let (mut t_enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let inv_direction = 1.0 / ray.direction.axis(axis);
+            let mut t0 =
+                (self.min.axis(axis) - ray.start.axis(axis)) * inv_direction;
+            let mut t1 =
+                (self.max.axis(axis) - ray.start.axis(axis)) * inv_direction;
+            if t0 > t1 {
+                ::std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                t_enter = t0;
+                enter_axis = axis;
+            }
+            if t1 < t_exit {
+                t_exit = t1;
+                exit_axis = axis;
+            }
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+        // The far side is hit when the ray starts inside the box
+        let (t, axis) = if t_enter > EPSILON {
+            (t_enter, enter_axis)
+        } else {
+            (t_exit, exit_axis)
+        };
+        if t <= EPSILON {
+            return None;
+        }
+        let point = ray.eval(t)?;
+
+        let center = (self.min.axis(axis) + self.max.axis(axis)) / 2.0;
+        let sign = if point.axis(axis) > center { 1.0 } else { -1.0 };
+        let normal = match axis {
+            0 => Vector3::new(sign, 0.0, 0.0),
+            1 => Vector3::new(0.0, sign, 0.0),
+            _ => Vector3::new(0.0, 0.0, sign),
+        };
+        let (u_axis, v_axis) = UV_AXES[axis];
+        let across = |axis: usize| {
+            (point.axis(axis) - self.min.axis(axis))
+                / (self.max.axis(axis) - self.min.axis(axis))
+        };
+        Some(
+            Intersection::new(normal, point)
+                .with_uv((across(u_axis), across(v_axis)))
+                .with_local_point(point - self.min),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::new(self.min, self.max))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        if !self.material.is_emissive() {
+            return None;
+        }
+        // Two triangles per face, each face given by the corners (as in
+        // `corner`) around it
+        let faces = [
+            [0, 2, 6, 4],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 3, 7, 6],
+            [0, 1, 3, 2],
+            [4, 5, 7, 6],
+        ];
+        let triangles = faces
+            .iter()
+            .flat_map(|&[a, b, c, d]| {
+                let [a, b, c, d] = [
+                    self.corner(a),
+                    self.corner(b),
+                    self.corner(c),
+                    self.corner(d),
+                ];
+                vec![[a, b, c], [a, c, d]]
+            })
+            .collect();
+        Some(Box::new(MeshLight::new(
+            self.material.emitted(),
+            triangles,
+            samples,
+        )))
+    }
+
+    fn info(&self) -> String {
+        format!("Cuboid: {:?} {:?}", self.min, self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_faces_with_normals_and_uvs() {
+        let cuboid = Cuboid::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Vector3::new(-1.0, 0.0, 1.0),
+            Material::default(),
+        );
+
+        // Into the -z face, a quarter of the way across in x and y
+        let ray = Ray::new(
+            Vector3::new(-0.5, 0.5, -5.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let hit = cuboid.intersects(&ray).unwrap();
+        assert!((hit.point.z - 1.0).abs() < 1e-9);
+        assert_eq!(hit.surface_normal, Vector3::new(0.0, 0.0, -1.0));
+        assert!((hit.uv.0 - 0.25).abs() < 1e-9);
+        assert!((hit.uv.1 - 0.25).abs() < 1e-9);
+
+        // From inside, out through the +y face
+        let ray =
+            Ray::new(Vector3::new(0.0, 1.0, 2.0), Vector3::new(0.2, 1.0, 0.1));
+        let hit = cuboid.intersects(&ray).unwrap();
+        assert!((hit.point.y - 2.0).abs() < 1e-9);
+        assert_eq!(hit.surface_normal, Vector3::new(0.0, 1.0, 0.0));
+
+        // Passing beside it
+        let ray =
+            Ray::new(Vector3::new(1.5, 1.0, -5.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(cuboid.intersects(&ray).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! A flat disk, or an annulus (a disk with a hole in the middle)
+
+use std::f64::consts::PI;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::lights::area_light::{AreaLight, LightShape};
+use crate::lights::light::Light;
+use crate::material::Material;
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::sampler::tangents;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+#[derive(Debug)]
+pub struct Disk {
+    pub center: Vector3,
+    /// Unit normal
+    pub normal: Vector3,
+    pub radius: f64,
+    /// Radius of the hole in the middle; 0 for a whole disk
+    pub inner_radius: f64,
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(
+        center: Vector3,
+        normal: Vector3,
+        radius: f64,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            normal: normal.normalized(),
+            radius,
+            inner_radius: 0.0,
+            material,
+        }
+    }
+
+    /// Cut a hole out of the middle, making an annulus
+    pub fn with_inner_radius(mut self, inner_radius: f64) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+}
+
+impl Object for Disk {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let t = ring_distance(
+            ray,
+            self.center,
+            self.normal,
+            self.inner_radius,
+            self.radius,
+        )?;
+        let point = ray.eval(t)?;
+        let uv = polar_uv(
+            point - self.center,
+            self.normal,
+            self.inner_radius,
+            self.radius,
+        );
+        Some(
+            Intersection::new(self.normal, point)
+                .with_uv(uv)
+                .with_local_point(point - self.center),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(disk_bounds(self.center, self.normal, self.radius))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        // Area lights can't have holes
+        if !self.material.is_emissive() || self.inner_radius > 0.0 {
+            return None;
+        }
+        // Seen from straight on, the disk glows with its whole area
+        let color = self.material.emitted() * (self.radius * self.radius);
+        let shape = LightShape::Disk {
+            // Slightly in front, so shadow rays don't hit the disk itself
+            center: self.center + self.normal * EPSILON,
+            normal: self.normal,
+            radius: self.radius,
+        };
+        Some(Box::new(AreaLight::new(color, shape, samples)))
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "Disk: {:?} {:?} {:?} {:?}",
+            self.center, self.normal, self.inner_radius, self.radius
+        )
+    }
+}
+
+/// Distance along the ray to where it crosses the ring between
+/// `inner_radius` and `radius` around `center`, on the plane through it
+/// with unit normal `normal`
+pub fn ring_distance(
+    ray: &Ray,
+    center: Vector3,
+    normal: Vector3,
+    inner_radius: f64,
+    radius: f64,
+) -> Option<f64> {
+    let t = (center - ray.start).dot(&normal) / ray.direction.dot(&normal);
+    // NaN when the ray runs along the plane
+    if t.is_nan() || t <= EPSILON {
+        return None;
+    }
+    let offset = ray.start + ray.direction * t - center;
+    let distance2 = offset.dot(&offset);
+    if distance2 > radius * radius || distance2 < inner_radius * inner_radius {
+        return None;
+    }
+    Some(t)
+}
+
+/// Texture coordinates for a point `offset` from the center of a ring: `u`
+/// goes around it, `v` from the inner edge to the outer
+pub fn polar_uv(
+    offset: Vector3,
+    normal: Vector3,
+    inner_radius: f64,
+    radius: f64,
+) -> (f64, f64) {
+    let (tangent, bitangent) = tangents(normal);
+    let angle = offset.dot(&bitangent).atan2(offset.dot(&tangent));
+    let distance = (offset - normal * offset.dot(&normal)).length();
+    (
+        0.5 + angle / (2.0 * PI),
+        (distance - inner_radius) / (radius - inner_radius),
+    )
+}
+
+/// Bounds of a circle around `center`, perpendicular to unit `normal`
+pub fn disk_bounds(
+    center: Vector3,
+    normal: Vector3,
+    radius: f64,
+) -> BoundingBox {
+    let extent = |axis: f64| radius * (1.0 - axis * axis).max(0.0).sqrt();
+    let extent =
+        Vector3::new(extent(normal.x), extent(normal.y), extent(normal.z));
+    BoundingBox::new(center - extent, center + extent)
+}
@@ -0,0 +1,214 @@
+//! An object moved, rotated or scaled by a transform
+//!
+//! Rays are brought into the object's own space to be intersected, and the
+//! hit is brought back out. The wrapped object can be shared, so many
+//! instances of one mesh only store it once.
+
+use std::sync::Arc;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::lights::light::Light;
+use crate::lights::transformed_light::TransformedLight;
+use crate::material::Material;
+use crate::matrix::Matrix4;
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+#[derive(Debug)]
+pub struct Instance {
+    pub object: Arc<Object>,
+    /// From the object's space to the world
+    pub transform: Matrix4,
+    /// From the world to the object's space
+    inverse: Matrix4,
+    /// Transforms normals to the world
+    normal_transform: Matrix4,
+}
+
+impl Instance {
+    /// `None` if the transform can't be inverted
+    pub fn new(object: Arc<Object>, transform: Matrix4) -> Option<Self> {
+        let inverse = transform.inverse()?;
+        Some(Self {
+            object,
+            transform,
+            inverse,
+            normal_transform: inverse.transposed(),
+        })
+    }
+}
+
+impl Object for Instance {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let direction = self.inverse.transform_direction(ray.direction);
+        // How much longer a distance is in the object's space
+        let stretch = direction.length();
+        let mut local_ray = Ray::with_t_max(
+            self.inverse.transform_point(ray.start),
+            direction,
+            ray.t_max * stretch,
+        );
+        local_ray.t_min = ray.t_min * stretch;
+
+        let hit = self.object.intersects(&local_ray)?;
+        let to_world = |normal| {
+            self.normal_transform
+                .transform_direction(normal)
+                .normalized()
+        };
+        Some(Intersection {
+            surface_normal: to_world(hit.surface_normal),
+            face_normal: to_world(hit.face_normal),
+            point: self.transform.transform_point(hit.point),
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let bounds = self.object.bounding_box()?;
+        let corners: Vec<_> = (0..8)
+            .map(|corner| {
+                let pick = |bit: usize, axis: usize| {
+                    if corner & bit == 0 {
+                        bounds.min.axis(axis)
+                    } else {
+                        bounds.max.axis(axis)
+                    }
+                };
+                self.transform.transform_point(Vector3::new(
+                    pick(1, 0),
+                    pick(2, 1),
+                    pick(4, 2),
+                ))
+            })
+            .collect();
+        Some(BoundingBox::from_points(&corners))
+    }
+
+    fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        let light = self.object.light(samples)?;
+        let light = TransformedLight::new(light, self.transform)
+            .expect("instances only have invertible transforms");
+        if !light.scales_evenly() {
+            warn!(
+                "{} glows but is stretched unevenly, so the light it gives \
+                 is only roughly as bright as it looks",
+                self.info()
+            );
+        }
+        Some(Box::new(light))
+    }
+
+    fn info(&self) -> String {
+        format!("Instance: {:?} of {}", self.transform, self.object.info())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::objects::sphere::Sphere;
+    use crate::pixel::Pixel;
+
+    #[test]
+    fn scaled_sphere_is_an_ellipsoid() {
+        let sphere = Sphere::new(1.0, Vector3::default(), Material::default());
+        let transform = Matrix4::translation(Vector3::new(0.0, 0.0, 10.0))
+            * Matrix4::scaling(Vector3::new(4.0, 1.0, 2.0));
+        let ellipsoid = Instance::new(Arc::new(sphere), transform).unwrap();
+
+        let ray = Ray::new(Vector3::default(), Vector3::new(0.0, 0.0, 1.0));
+        let hit = ellipsoid.intersects(&ray).unwrap();
+        assert!((hit.point - Vector3::new(0.0, 0.0, 8.0)).length() < 1e-9);
+
+        // Where x^2 / 16 + (z - 10)^2 / 4 = 1 meets x = 2, the normal is
+        // along (x / 16, 0, (z - 10) / 4)
+        let ray =
+            Ray::new(Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = ellipsoid.intersects(&ray).unwrap();
+        let z = 10.0 - 3.0_f64.sqrt();
+        assert!((hit.point.z - z).abs() < 1e-9);
+        let expected = Vector3::new(2.0 / 16.0, 0.0, (z - 10.0) / 4.0);
+        assert!((hit.surface_normal - expected.normalized()).length() < 1e-9);
+
+        let bounds = ellipsoid.bounding_box().unwrap();
+        assert!((bounds.min - Vector3::new(-4.0, -1.0, 8.0)).length() < 1e-9);
+        assert!((bounds.max - Vector3::new(4.0, 1.0, 12.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn scaled_glowing_sphere_lights_like_a_bigger_one() {
+        let glowing = Material {
+            // Dim enough that highlights aren't clamped
+            emission: Pixel::from_rgb(0.2, 0.2, 0.2),
+            ..Material::default()
+        };
+        let small = Sphere::new(1.0, Vector3::default(), glowing.clone());
+        let transform = Matrix4::translation(Vector3::new(0.0, 10.0, 0.0))
+            * Matrix4::scaling(Vector3::new(2.0, 2.0, 2.0));
+        let scaled = Instance::new(Arc::new(small), transform).unwrap();
+        let big = Sphere::new(2.0, Vector3::new(0.0, 10.0, 0.0), glowing);
+
+        let intersection =
+            Intersection::new(Vector3::new(0.0, 1.0, 0.0), Vector3::default());
+        let lit = Material {
+            diffuse: Pixel::from_rgb(1.0, 1.0, 1.0),
+            specular: Pixel::from_rgb(1.0, 1.0, 1.0),
+            phong_power: 2.0,
+            ..Material::default()
+        };
+        let camera = Camera {
+            position: Vector3::new(0.0, 5.0, -5.0),
+            ..Camera::default()
+        };
+        let brightness = |light: Box<Light>| {
+            let mut total = 0.0;
+            for i in 0..8 {
+                for j in 0..8 {
+                    let random =
+                        ((i as f64 + 0.5) / 8.0, (j as f64 + 0.5) / 8.0);
+                    let sample = light.sample(&intersection, random);
+                    total += light.diffuse(&intersection, &lit, &sample).r;
+                    total +=
+                        light.specular(&camera, &intersection, &lit, &sample).r;
+                }
+            }
+            total
+        };
+        let expected = brightness(big.light(1).unwrap());
+        let actual = brightness(scaled.light(1).unwrap());
+        assert!(expected > 0.0);
+        assert!((actual - expected).abs() < 1e-3 * expected, "{}", actual);
+    }
+
+    #[test]
+    fn only_even_scaling_lights_exactly() {
+        let glowing = Material {
+            emission: Pixel::from_rgb(1.0, 1.0, 1.0),
+            ..Material::default()
+        };
+        let sphere = Sphere::new(1.0, Vector3::default(), glowing);
+        let light = |transform| {
+            TransformedLight::new(sphere.light(1).unwrap(), transform).unwrap()
+        };
+        let turned = Matrix4::rotation(Vector3::new(1.0, 1.0, 0.0), 0.7)
+            * Matrix4::scaling(Vector3::new(3.0, 3.0, -3.0));
+        assert!(light(turned).scales_evenly());
+        // An ellipsoid
+        let stretched = Matrix4::scaling(Vector3::new(2.0, 1.0, 1.0));
+        assert!(!light(stretched).scales_evenly());
+        let sheared = Matrix4::from_rows([
+            [1.0, 0.5, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]);
+        assert!(!light(sheared).scales_evenly());
+    }
+}
@@ -1,6 +1,15 @@
 //! All objects that can be represented by this ray tracer
 
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod disk;
+pub mod instance;
 pub mod object;
 pub mod plane;
+pub mod quadric;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
 pub mod triangle;
+pub mod triangle_mesh;
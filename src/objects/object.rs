@@ -4,15 +4,55 @@
 
 use std::fmt;
 
+use crate::bounding_box::BoundingBox;
 use crate::intersection::Intersection;
+use crate::lights::light::Light;
 use crate::material::Material;
+use crate::objects::csg::{Crossing, Span};
 use crate::ray::Ray;
 
-pub trait Object {
+/// Most surface crossings looked for along one ray by the default `spans`
+const MAX_CROSSINGS: usize = 32;
+
+/// Objects are shared between render threads, so they must be `Send` and
+/// `Sync`
+pub trait Object: Send + Sync {
     fn intersects(&self, ray: &Ray) -> Option<Intersection>;
 
+    /// Every stretch of the ray inside the object, in order, for combining
+    /// solids. By default the surface is found again just past each hit,
+    /// which works for closed objects whose normals point out.
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let mut crossings = Vec::new();
+        let mut rest = *ray;
+        while crossings.len() < MAX_CROSSINGS {
+            let intersection = match self.intersects(&rest) {
+                Some(intersection) => intersection,
+                None => break,
+            };
+            let crossing = Crossing::new(ray, intersection);
+            rest = Ray::with_t_max(
+                crossing.intersection.point,
+                ray.direction,
+                ray.t_max - crossing.t,
+            );
+            crossings.push(crossing);
+        }
+        Span::from_crossings(crossings)
+    }
+
+    /// Bounds of the object, or `None` if it is infinite (e.g. a plane)
+    fn bounding_box(&self) -> Option<BoundingBox>;
+
     fn material(&self) -> &Material;
 
+    /// A light standing in for the object when its material is emissive,
+    /// taking `samples` shadow rays per intersection. Objects that can't be
+    /// sampled (e.g. planes) glow without lighting anything.
+    fn light(&self, _samples: usize) -> Option<Box<Light>> {
+        None
+    }
+
     fn info(&self) -> String;
 }
 
@@ -1,5 +1,6 @@
 //! A plane
 
+use crate::bounding_box::BoundingBox;
 use crate::intersection::Intersection;
 use crate::material::Material;
 use crate::objects::object::Object;
@@ -12,16 +13,54 @@ pub struct Plane {
     pub point: Vector3,
     pub normal: Vector3,
     pub material: Material,
+
+    /// Directions of increasing texture coordinates along the plane
+    pub u_axis: Vector3,
+    pub v_axis: Vector3,
+
+    /// Distance over which the texture coordinates go from 0 to 1
+    pub uv_scale: f64,
 }
 
 impl Plane {
     pub fn new(material: Material, point: Vector3, normal: Vector3) -> Self {
+        let normal = normal.normalized();
+        // Any direction along the plane will do; use the world axis that is
+        // furthest from the normal
+        let axis = if normal.x.abs() < normal.y.abs().min(normal.z.abs()) {
+            Vector3::new(1.0, 0.0, 0.0)
+        } else if normal.y.abs() < normal.z.abs() {
+            Vector3::new(0.0, 1.0, 0.0)
+        } else {
+            Vector3::new(0.0, 0.0, 1.0)
+        };
+        let (u_axis, v_axis) = uv_axes(normal, axis);
         Self {
             point,
-            normal: normal.normalized(),
+            normal,
             material,
+            u_axis,
+            v_axis,
+            uv_scale: 1.0,
         }
     }
+
+    /// Lay out texture coordinates with `u` increasing along `u_axis`
+    /// (projected onto the plane) and repeating every `scale` units
+    pub fn with_uv_axis(mut self, u_axis: Vector3, scale: f64) -> Self {
+        let (u_axis, v_axis) = uv_axes(self.normal, u_axis);
+        self.u_axis = u_axis;
+        self.v_axis = v_axis;
+        self.uv_scale = scale;
+        self
+    }
+}
+
+/// Texture axes along a plane with unit `normal`, with `u` along `u_axis`
+/// projected onto the plane
+fn uv_axes(normal: Vector3, u_axis: Vector3) -> (Vector3, Vector3) {
+    let u_axis = (u_axis - normal * u_axis.dot(&normal)).normalized();
+    (u_axis, normal.cross(&u_axis))
 }
 
 impl Object for Plane {
@@ -35,12 +74,25 @@ impl Object for Plane {
                 return None;
             }
             let p = p.unwrap();
-            Some(Intersection::new(self.normal, p))
+            let offset = p - self.point;
+            let uv = (
+                offset.dot(&self.u_axis) / self.uv_scale,
+                offset.dot(&self.v_axis) / self.uv_scale,
+            );
+            Some(
+                Intersection::new(self.normal, p)
+                    .with_uv(uv)
+                    .with_local_point(offset),
+            )
         } else {
             None
         }
     }
 
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        None
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
@@ -49,3 +101,22 @@ impl Object for Plane {
         format!("Plane: {:?} {:?}", self.point, self.normal)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_axes_lie_along_the_plane() {
+        let plane = Plane::new(
+            Material::default(),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 2.0, 3.0),
+        );
+        for axis in &[plane.u_axis, plane.v_axis] {
+            assert!((axis.length() - 1.0).abs() < 1e-9);
+            assert!(axis.dot(&plane.normal).abs() < 1e-9);
+        }
+        assert!(plane.u_axis.dot(&plane.v_axis).abs() < 1e-9);
+    }
+}
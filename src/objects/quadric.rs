@@ -0,0 +1,165 @@
+//! A general quadric surface, the points where
+//!
+//! a x^2 + b y^2 + c z^2 + d xy + e xz + f yz + g x + h y + i z + j = 0
+//!
+//! Ellipsoids, paraboloids, hyperboloids, cones and cylinders are all
+//! quadrics. Most of them go on forever, so they can be clipped to a box.
+//!
+//! The surface faces the side where the left hand side is positive, and
+//! that side counts as outside for glass and `csg`. Sign the coefficients
+//! so that the inside is where it's negative, e.g. x^2 + y^2 + z^2 - 1 for
+//! a unit sphere rather than 1 - x^2 - y^2 - z^2.
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::objects::object::Object;
+use crate::polynomial::real_roots;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+#[derive(Debug)]
+pub struct Quadric {
+    /// a to j, in the order of the equation above
+    pub coefficients: [f64; 10],
+    /// Only the part of the surface inside this box is kept
+    pub clip: Option<BoundingBox>,
+    pub material: Material,
+}
+
+impl Quadric {
+    pub fn new(coefficients: [f64; 10], material: Material) -> Self {
+        Self {
+            coefficients,
+            clip: None,
+            material,
+        }
+    }
+
+    pub fn with_clip(mut self, clip: BoundingBox) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Direction in which the equation grows fastest, which is normal to
+    /// the surface
+    fn gradient(&self, point: Vector3) -> Vector3 {
+        let [a, b, c, d, e, f, g, h, i, _] = self.coefficients;
+        let Vector3 { x, y, z } = point;
+        Vector3::new(
+            2.0 * a * x + d * y + e * z + g,
+            2.0 * b * y + d * x + f * z + h,
+            2.0 * c * z + e * x + f * y + i,
+        )
+    }
+
+    fn is_kept(&self, point: Vector3) -> bool {
+        match self.clip {
+            Some(BoundingBox { min, max }) => (0..3).all(|axis| {
+                point.axis(axis) >= min.axis(axis)
+                    && point.axis(axis) <= max.axis(axis)
+            }),
+            None => true,
+        }
+    }
+}
+
+impl Object for Quadric {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let [a, b, c, d, e, f, g, h, i, j] = self.coefficients;
+        let (o, v) = (ray.start, ray.direction);
+        // Substitute the ray into the equation, giving a quadratic in t
+        let t2 = a * v.x * v.x
+            + b * v.y * v.y
+            + c * v.z * v.z
+            + d * v.x * v.y
+            + e * v.x * v.z
+            + f * v.y * v.z;
+        let t1 = 2.0 * (a * o.x * v.x + b * o.y * v.y + c * o.z * v.z)
+            + d * (o.x * v.y + o.y * v.x)
+            + e * (o.x * v.z + o.z * v.x)
+            + f * (o.y * v.z + o.z * v.y)
+            + g * v.x
+            + h * v.y
+            + i * v.z;
+        let t0 = a * o.x * o.x
+            + b * o.y * o.y
+            + c * o.z * o.z
+            + d * o.x * o.y
+            + e * o.x * o.z
+            + f * o.y * o.z
+            + g * o.x
+            + h * o.y
+            + i * o.z
+            + j;
+
+        // Behind a clipped part, the surface can be seen through it
+        let point = real_roots(&[t0, t1, t2])
+            .into_iter()
+            .filter(|&t| t > EPSILON)
+            .filter_map(|t| ray.eval(t))
+            .find(|&point| self.is_kept(point))?;
+        Some(
+            Intersection::new(self.gradient(point).normalized(), point)
+                .with_local_point(point),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.clip
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn info(&self) -> String {
+        format!("Quadric: {:?} {:?}", self.coefficients, self.clip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipped_paraboloid() {
+        // y = x^2 + z^2, cut off at y = 4 and with a slice taken off the
+        // side at x = -1.5
+        let paraboloid = Quadric::new(
+            [1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0],
+            Material::default(),
+        )
+        .with_clip(BoundingBox::new(
+            Vector3::new(-1.5, 0.0, -2.0),
+            Vector3::new(2.0, 4.0, 2.0),
+        ));
+
+        let down = Ray::new(
+            Vector3::new(1.0, 10.0, 0.0),
+            Vector3::new(0.0, -1.0, 0.0),
+        );
+        let hit = paraboloid.intersects(&down).unwrap();
+        assert!((hit.point.y - 1.0).abs() < 1e-9);
+        // The gradient (2x, -1, 2z) points out of the bowl, downwards
+        let expected = Vector3::new(2.0, -1.0, 0.0).normalized();
+        assert!((hit.surface_normal - expected).length() < 1e-9);
+
+        // Where the near wall is cut away, the inside of the far wall is hit
+        // through the gap
+        let across = Ray::new(
+            Vector3::new(-10.0, 3.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+        let hit = paraboloid.intersects(&across).unwrap();
+        assert!((hit.point.x - 3.0_f64.sqrt()).abs() < 1e-9);
+        // Above the rim there is nothing left
+        let across = Ray::new(
+            Vector3::new(-10.0, 5.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+        assert!(paraboloid.intersects(&across).is_none());
+    }
+}
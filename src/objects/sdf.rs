@@ -0,0 +1,346 @@
+//! Shapes described by a signed distance field: a function giving the
+//! distance from any point to the nearest surface, negative inside
+//!
+//! Distance fields can be blended smoothly and repeated endlessly, which
+//! makes shapes with no formula for where a ray hits them. Instead, rays
+//! are sphere traced: stepping forward by the distance to the nearest
+//! surface never steps through it, so the ray creeps up on the hit.
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::objects::object::Object;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+/// Most steps taken along one ray before giving up
+const MAX_STEPS: usize = 256;
+
+/// How close to the surface counts as hitting it
+const HIT_DISTANCE: f64 = 1e-5;
+
+/// Offset used to estimate the normal from the change in distance
+const NORMAL_STEP: f64 = 1e-5;
+
+/// A distance field, built from primitives and operators
+#[derive(Debug, Clone)]
+pub enum SdfShape {
+    Sphere {
+        center: Vector3,
+        radius: f64,
+    },
+    /// A box with edges rounded off by `rounding`
+    Box {
+        center: Vector3,
+        half_size: Vector3,
+        rounding: f64,
+    },
+    /// A torus around the y axis
+    Torus {
+        center: Vector3,
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    /// Every point within `radius` of the segment from `start` to `end`
+    Capsule {
+        start: Vector3,
+        end: Vector3,
+        radius: f64,
+    },
+    /// Inside either, blended over a distance of about `k`
+    SmoothUnion(Box<SdfShape>, Box<SdfShape>, f64),
+    /// Inside both, blended over a distance of about `k`
+    SmoothIntersection(Box<SdfShape>, Box<SdfShape>, f64),
+    /// The first with the second carved out of it, blended over a distance
+    /// of about `k`
+    SmoothSubtraction(Box<SdfShape>, Box<SdfShape>, f64),
+    /// Copies of the shape every `period` along each axis, or just one
+    /// along axes where the period is 0. The shape should fit in one cell.
+    Repeat(Box<SdfShape>, Vector3),
+}
+
+impl SdfShape {
+    /// Distance from `point` to the surface, negative inside. Blending
+    /// makes it an underestimate in places, which only slows tracing down.
+    pub fn distance(&self, point: Vector3) -> f64 {
+        match self {
+            SdfShape::Sphere { center, radius } => {
+                (point - *center).length() - radius
+            }
+            SdfShape::Box {
+                center,
+                half_size,
+                rounding,
+            } => {
+                let offset = point - *center;
+                let q = Vector3::new(
+                    offset.x.abs() - (half_size.x - rounding),
+                    offset.y.abs() - (half_size.y - rounding),
+                    offset.z.abs() - (half_size.z - rounding),
+                );
+                let outside =
+                    Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+                outside.length() + q.x.max(q.y).max(q.z).min(0.0) - rounding
+            }
+            SdfShape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let offset = point - *center;
+                let around = offset.x.hypot(offset.z) - major_radius;
+                around.hypot(offset.y) - minor_radius
+            }
+            SdfShape::Capsule { start, end, radius } => {
+                let (offset, segment) = (point - *start, *end - *start);
+                let along = (offset.dot(&segment) / segment.dot(&segment))
+                    .clamp(0.0, 1.0);
+                (offset - segment * along).length() - radius
+            }
+            SdfShape::SmoothUnion(a, b, k) => {
+                smooth_min(a.distance(point), b.distance(point), *k)
+            }
+            SdfShape::SmoothIntersection(a, b, k) => {
+                -smooth_min(-a.distance(point), -b.distance(point), *k)
+            }
+            SdfShape::SmoothSubtraction(a, b, k) => {
+                -smooth_min(-a.distance(point), b.distance(point), *k)
+            }
+            SdfShape::Repeat(shape, period) => {
+                // Fold space into the cell around the origin
+                let wrap = |value: f64, period: f64| {
+                    if period > 0.0 {
+                        value - period * (value / period).round()
+                    } else {
+                        value
+                    }
+                };
+                shape.distance(Vector3::new(
+                    wrap(point.x, period.x),
+                    wrap(point.y, period.y),
+                    wrap(point.z, period.z),
+                ))
+            }
+        }
+    }
+
+    /// Bounds of the shape, or `None` if it repeats forever
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        let around = |center: Vector3, extent: Vector3| {
+            Some(BoundingBox::new(center - extent, center + extent))
+        };
+        // Blending can swell the surface by up to `k / 4`
+        let grow = |bounds: BoundingBox, k: f64| {
+            let k = Vector3::new(1.0, 1.0, 1.0) * (k / 4.0);
+            Some(BoundingBox::new(bounds.min - k, bounds.max + k))
+        };
+        match self {
+            SdfShape::Sphere { center, radius } => {
+                around(*center, Vector3::new(1.0, 1.0, 1.0) * *radius)
+            }
+            SdfShape::Box {
+                center, half_size, ..
+            } => around(*center, *half_size),
+            SdfShape::Torus {
+                center,
+                major_radius,
+                minor_radius,
+            } => {
+                let outer = major_radius + minor_radius;
+                around(*center, Vector3::new(outer, *minor_radius, outer))
+            }
+            SdfShape::Capsule { start, end, radius } => {
+                let extent = Vector3::new(1.0, 1.0, 1.0) * *radius;
+                Some(BoundingBox::from_points(&[
+                    *start - extent,
+                    *start + extent,
+                    *end - extent,
+                    *end + extent,
+                ]))
+            }
+            SdfShape::SmoothUnion(a, b, k) => {
+                grow(a.bounds()?.union(&b.bounds()?), *k)
+            }
+            SdfShape::SmoothIntersection(a, _, k)
+            | SdfShape::SmoothSubtraction(a, _, k) => grow(a.bounds()?, *k),
+            SdfShape::Repeat(shape, period) => {
+                if period.x > 0.0 || period.y > 0.0 || period.z > 0.0 {
+                    None
+                } else {
+                    shape.bounds()
+                }
+            }
+        }
+    }
+}
+
+/// The smaller of `a` and `b`, with the corner where they meet rounded
+/// over a distance of about `k`
+fn smooth_min(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k / 4.0
+}
+
+/// How the shapes in an `sdf` group are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SdfOperation {
+    SmoothUnion(f64),
+    SmoothIntersection(f64),
+    /// Carve all the other shapes out of the first
+    SmoothSubtraction(f64),
+    /// Repeat the union of the shapes
+    Repeat(Vector3),
+}
+
+impl SdfOperation {
+    /// Combine `shapes` from the first onwards. There must be at least one.
+    pub fn combine(self, shapes: Vec<SdfShape>) -> SdfShape {
+        let mut shapes = shapes.into_iter().map(Box::new);
+        let first = shapes.next().expect("no shapes to combine");
+        let combined = shapes.fold(first, |a, b| {
+            Box::new(match self {
+                SdfOperation::SmoothUnion(k) => SdfShape::SmoothUnion(a, b, k),
+                SdfOperation::SmoothIntersection(k) => {
+                    SdfShape::SmoothIntersection(a, b, k)
+                }
+                SdfOperation::SmoothSubtraction(k) => {
+                    SdfShape::SmoothSubtraction(a, b, k)
+                }
+                SdfOperation::Repeat(_) => SdfShape::SmoothUnion(a, b, 0.0),
+            })
+        });
+        match self {
+            SdfOperation::Repeat(period) => SdfShape::Repeat(combined, period),
+            _ => *combined,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Sdf {
+    pub shape: SdfShape,
+    pub material: Material,
+}
+
+impl Sdf {
+    pub fn new(shape: SdfShape, material: Material) -> Self {
+        Self { shape, material }
+    }
+
+    /// Direction in which the distance grows fastest, which is normal to
+    /// the surface
+    fn normal(&self, point: Vector3) -> Vector3 {
+        let change = |axis: Vector3| {
+            self.shape.distance(point + axis * NORMAL_STEP)
+                - self.shape.distance(point - axis * NORMAL_STEP)
+        };
+        Vector3::new(
+            change(Vector3::new(1.0, 0.0, 0.0)),
+            change(Vector3::new(0.0, 1.0, 0.0)),
+            change(Vector3::new(0.0, 0.0, 1.0)),
+        )
+        .normalized()
+    }
+}
+
+impl Object for Sdf {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        // Only trace where the ray is within the bounds
+        let (mut t, end) = match self.shape.bounds() {
+            Some(bounds) => {
+                let (enter, exit) = bounds.intersects(ray)?;
+                (enter.max(EPSILON), exit)
+            }
+            None => (EPSILON, ray.t_max),
+        };
+
+        // Rays starting inside (e.g. refracted ones) trace their way out
+        let side = if self.shape.distance(ray.start + ray.direction * t) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        for _ in 0..MAX_STEPS {
+            let distance =
+                side * self.shape.distance(ray.start + ray.direction * t);
+            if distance < HIT_DISTANCE {
+                let point = ray.eval(t)?;
+                return Some(Intersection::new(self.normal(point), point));
+            }
+            t += distance;
+            if t > end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.shape.bounds()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn info(&self) -> String {
+        format!("Sdf: {:?}", self.shape)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traces_blends_and_repeats() {
+        let sphere = SdfShape::Sphere {
+            center: Vector3::new(0.0, 0.0, 5.0),
+            radius: 1.0,
+        };
+        let sdf = Sdf::new(sphere.clone(), Material::default());
+        let ray =
+            Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = sdf.intersects(&ray).unwrap();
+        assert!((hit.point.z - 4.0).abs() < 1e-4);
+        assert!((hit.surface_normal.z + 1.0).abs() < 1e-6);
+        // From inside, the far side
+        let inside =
+            Ray::new(Vector3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = sdf.intersects(&inside).unwrap();
+        assert!((hit.point.z - 6.0).abs() < 1e-4);
+
+        // Blending two spheres fills in the gap where they meet
+        let other = SdfShape::Sphere {
+            center: Vector3::new(2.2, 0.0, 5.0),
+            radius: 1.0,
+        };
+        let blend = SdfOperation::SmoothUnion(1.0)
+            .combine(vec![sphere.clone(), other.clone()]);
+        let between = Vector3::new(1.1, 0.0, 5.0);
+        assert!(
+            SdfOperation::SmoothUnion(0.0)
+                .combine(vec![sphere.clone(), other])
+                .distance(between)
+                > 0.0
+        );
+        assert!(blend.distance(between) < 0.0);
+
+        // A row of spheres 3 apart along x
+        let row = Sdf::new(
+            SdfOperation::Repeat(Vector3::new(3.0, 0.0, 0.0))
+                .combine(vec![sphere]),
+            Material::default(),
+        );
+        assert!(row.bounding_box().is_none());
+        let ray =
+            Ray::new(Vector3::new(30.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        let hit = row.intersects(&ray).unwrap();
+        assert!((hit.point.z - 4.0).abs() < 1e-4);
+    }
+}
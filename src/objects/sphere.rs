@@ -1,6 +1,11 @@
 //! Representation of a sphere to be ray traced
 
+use std::f64::consts::PI;
+
+use crate::bounding_box::BoundingBox;
 use crate::intersection::Intersection;
+use crate::lights::area_light::{AreaLight, LightShape};
+use crate::lights::light::Light;
 use crate::material::Material;
 use crate::objects::object::Object;
 use crate::ray::Ray;
@@ -37,33 +42,87 @@ impl Object for Sphere {
         let discriminant = b * b - 4.0 * a * c;
 
         if discriminant >= 0.0 {
-            let t1 = (-b + discriminant.sqrt()) / (2.0 * a);
-            let t2 = (-b - discriminant.sqrt()) / (2.0 * a);
-            let p1 = ray.eval(t1);
-            let p2 = ray.eval(t2);
-            if p1.is_some() && t1 < t2 && t1 > EPSILON {
-                Some(Intersection::new(
-                    (p1.unwrap() - self.position).normalized(),
-                    p1.unwrap(),
-                ))
-            } else if p2.is_some() && t1 >= t2 && t2 > EPSILON {
-                Some(Intersection::new(
-                    (p2.unwrap() - self.position).normalized(),
-                    p2.unwrap(),
-                ))
-            } else {
-                None
+            let near = (-b - discriminant.sqrt()) / (2.0 * a);
+            let far = (-b + discriminant.sqrt()) / (2.0 * a);
+            // The far side is hit when the ray starts inside the sphere
+            let t = if near > EPSILON { near } else { far };
+            if t <= EPSILON {
+                return None;
             }
+            let point = ray.eval(t)?;
+            let normal = (point - self.position).normalized();
+            Some(
+                Intersection::new(normal, point)
+                    .with_uv(spherical_uv(normal))
+                    .with_local_point(point - self.position),
+            )
         } else {
             None
         }
     }
 
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        let extent = Vector3::new(self.radius, self.radius, self.radius);
+        Some(BoundingBox::new(
+            self.position - extent,
+            self.position + extent,
+        ))
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        if !self.material.is_emissive() {
+            return None;
+        }
+        // Seen from far away, the sphere is a disk of this much area
+        // glowing straight at the viewer
+        let color = self.material.emitted() * (self.radius * self.radius);
+        // Slightly bigger, so shadow rays don't hit the sphere itself
+        let shape = LightShape::Sphere {
+            center: self.position,
+            radius: self.radius + EPSILON,
+        };
+        Some(Box::new(AreaLight::new(color, shape, samples)))
+    }
+
     fn info(&self) -> String {
         format!("Sphere: {:?} {:?}", self.position, self.radius)
     }
 }
+
+/// Texture coordinates for a direction from the center of a sphere: `u`
+/// goes around the equator starting at -x, `v` from the bottom (-y) to the
+/// top (+y)
+pub fn spherical_uv(direction: Vector3) -> (f64, f64) {
+    let u = 0.5 - direction.z.atan2(direction.x) / (2.0 * PI);
+    let v = 0.5 + direction.y.clamp(-1.0, 1.0).asin() / PI;
+    (u, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_from_outside_and_inside() {
+        let sphere =
+            Sphere::new(1.0, Vector3::new(0.0, 0.0, 5.0), Material::default());
+        let forward = Vector3::new(0.0, 0.0, 1.0);
+
+        let outside = Ray::new(Vector3::new(0.0, 0.0, 0.0), forward);
+        let hit = sphere.intersects(&outside).unwrap();
+        assert!((hit.point.z - 4.0).abs() < 1e-9);
+
+        let inside = Ray::new(Vector3::new(0.0, 0.0, 5.0), forward);
+        let hit = sphere.intersects(&inside).unwrap();
+        assert!((hit.point.z - 6.0).abs() < 1e-9);
+        // The normal still points out of the sphere
+        assert!(hit.surface_normal.z > 0.0);
+
+        let behind = Ray::new(Vector3::new(0.0, 0.0, 7.0), forward);
+        assert!(sphere.intersects(&behind).is_none());
+    }
+}
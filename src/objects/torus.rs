@@ -0,0 +1,175 @@
+//! A torus (a donut): a tube of radius `minor_radius` around a circle of
+//! radius `major_radius`
+
+use std::f64::consts::PI;
+
+use crate::bounding_box::BoundingBox;
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::objects::disk::disk_bounds;
+use crate::objects::object::Object;
+use crate::polynomial::{quadratic_roots, real_roots};
+use crate::ray::Ray;
+use crate::sampler::tangents;
+use crate::vector::Vector3;
+
+const EPSILON: f64 = 0.001;
+
+#[derive(Debug)]
+pub struct Torus {
+    pub center: Vector3,
+    /// Unit vector through the hole
+    pub axis: Vector3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Material,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector3,
+        axis: Vector3,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            axis: axis.normalized(),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+
+    /// A point or direction in the torus' own space, where the axis is y
+    fn to_local(&self, vector: Vector3) -> Vector3 {
+        let (tangent, bitangent) = tangents(self.axis);
+        Vector3::new(
+            vector.dot(&tangent),
+            vector.dot(&self.axis),
+            vector.dot(&bitangent),
+        )
+    }
+
+    fn to_world(&self, vector: Vector3) -> Vector3 {
+        let (tangent, bitangent) = tangents(self.axis);
+        tangent * vector.x + self.axis * vector.y + bitangent * vector.z
+    }
+}
+
+impl Object for Torus {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+        let start = self.to_local(ray.start - self.center);
+        let direction = self.to_local(ray.direction);
+
+        // Solving from far away loses precision, so first move the start
+        // up to the sphere around the torus
+        let bound = major + minor;
+        let to_center = start.dot(&direction);
+        let sphere = quadratic_roots(
+            1.0,
+            2.0 * to_center,
+            start.dot(&start) - bound * bound,
+        );
+        let skipped = match sphere.first() {
+            Some(&near) => near.max(0.0),
+            None => return None,
+        };
+        let start = start + direction * skipped;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2), along p = start + t d
+        let m = start.dot(&direction);
+        let n = start.dot(&start) + major * major - minor * minor;
+        let four_major2 = 4.0 * major * major;
+        let coefficients = [
+            n * n - four_major2 * (start.x * start.x + start.z * start.z),
+            4.0 * m * n
+                - 2.0
+                    * four_major2
+                    * (start.x * direction.x + start.z * direction.z),
+            4.0 * m * m + 2.0 * n
+                - four_major2
+                    * (direction.x * direction.x + direction.z * direction.z),
+            4.0 * m,
+            1.0,
+        ];
+        let t = real_roots(&coefficients)
+            .into_iter()
+            .map(|t| t + skipped)
+            .find(|&t| t > EPSILON)?;
+        let point = ray.eval(t)?;
+
+        // The normal points away from the nearest point on the circle
+        // through the middle of the tube
+        let local = self.to_local(point - self.center);
+        let around = Vector3::new(local.x, 0.0, local.z).normalized();
+        let local_normal = (local - around * major).normalized();
+        let uv = (
+            0.5 + local.z.atan2(local.x) / (2.0 * PI),
+            0.5 + local_normal.y.atan2(local_normal.dot(&around)) / (2.0 * PI),
+        );
+        Some(
+            Intersection::new(self.to_world(local_normal), point)
+                .with_uv(uv)
+                .with_local_point(point - self.center),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        // The circle through the middle of the tube, grown by the tube
+        let ring = disk_bounds(self.center, self.axis, self.major_radius);
+        let tube = Vector3::new(1.0, 1.0, 1.0) * self.minor_radius;
+        Some(BoundingBox::new(ring.min - tube, ring.max + tube))
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "Torus: {:?} {:?} {:?} {:?}",
+            self.center, self.axis, self.major_radius, self.minor_radius
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hits_outside_and_through_the_hole() {
+        let torus = Torus::new(
+            Vector3::new(0.0, 0.0, 10.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            2.0,
+            0.5,
+            Material::default(),
+        );
+        // Straight at the tube from far away
+        let ray = Ray::new(
+            Vector3::new(2.0, 0.0, -100.0),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+        let hit = torus.intersects(&ray).unwrap();
+        assert!((hit.point.z - 9.5).abs() < 1e-9, "{:?}", hit.point);
+        assert!((hit.surface_normal.z + 1.0).abs() < 1e-9);
+
+        // Across the ring, hitting the outside of the near tube
+        let ray = Ray::new(
+            Vector3::new(-5.0, 0.0, 10.0),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+        let hit = torus.intersects(&ray).unwrap();
+        assert!((hit.point.x + 2.5).abs() < 1e-9, "{:?}", hit.point);
+        assert!((hit.surface_normal.x + 1.0).abs() < 1e-9);
+
+        // Through the hole
+        let ray =
+            Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 1.0));
+        assert!(torus.intersects(&ray).is_none());
+    }
+}
@@ -1,7 +1,10 @@
 //! A single triangle
 
+use crate::bounding_box::BoundingBox;
 use crate::camera::Camera;
 use crate::intersection::Intersection;
+use crate::lights::light::Light;
+use crate::lights::mesh_light::MeshLight;
 use crate::material::Material;
 use crate::objects::object::Object;
 use crate::ray::Ray;
@@ -9,6 +12,9 @@ use crate::vector::Vector3;
 
 const EPSILON: f64 = 0.001;
 
+// Smallest determinant for which the ray isn't considered parallel
+const PARALLEL_TOLERANCE: f64 = 1e-12;
+
 // Disclaimer: I know this is a poor way to represent triangles - I just don't
 // have the time to make it good because I'll likely run out of time on more
 // important parts.
@@ -19,8 +25,9 @@ pub struct Triangle {
     pub n1: Vector3,
     pub n2: Vector3,
     pub n3: Vector3,
+    /// Per-vertex texture coordinates
+    pub texcoords: Option<[(f64, f64); 3]>,
     pub material: Material,
-    plane_normal: Vector3,
 }
 
 impl Triangle {
@@ -40,11 +47,16 @@ impl Triangle {
             n1,
             n2,
             n3,
+            texcoords: None,
             material,
-            plane_normal: (v1 - v2).cross(&(v3 - v2)).normalized(),
         }
     }
 
+    pub fn with_texcoords(mut self, texcoords: [(f64, f64); 3]) -> Self {
+        self.texcoords = Some(texcoords);
+        self
+    }
+
     pub fn guess_normal(
         material: Material,
         v1: Vector3,
@@ -52,12 +64,7 @@ impl Triangle {
         v3: Vector3,
         camera: &Camera,
     ) -> Self {
-        let guessed_normal = (v1 - v2).cross(&(v3 - v2)).normalized();
-        let guessed_normal = if guessed_normal.dot(&camera.direction) < 0.0 {
-            guessed_normal
-        } else {
-            -guessed_normal
-        };
+        let guessed_normal = guess_normal(v1, v2, v3, camera);
         Self::new(
             material,
             v1,
@@ -72,64 +79,229 @@ impl Triangle {
 
 impl Object for Triangle {
     fn intersects(&self, ray: &Ray) -> Option<Intersection> {
-        let d = self.v1.dot(&self.plane_normal);
-        let t = -(ray.start.dot(&self.plane_normal) - d)
-            / (ray.direction.dot(&self.plane_normal));
-        if t >= EPSILON {
-            let p = ray.eval(t);
-            if p.is_none() {
-                return None;
-            }
-            let p = p.unwrap();
-            if same_side(p, self.v1, self.v2, self.v3)
-                && same_side(p, self.v2, self.v1, self.v3)
-                && same_side(p, self.v3, self.v1, self.v2)
-            {
-                Some(Intersection::new(
-                    bary_interp(
-                        p, self.v1, self.v2, self.v3, self.n1, self.n2,
-                        self.n3,
-                    ),
-                    p,
-                ))
-            } else {
-                None
-            }
-        } else {
-            None
+        let intersection = intersect_triangle(
+            ray,
+            [self.v1, self.v2, self.v3],
+            [self.n1, self.n2, self.n3],
+        )?;
+        match self.texcoords {
+            Some(texcoords) => Some(with_texcoords(intersection, texcoords)),
+            None => Some(intersection),
         }
     }
 
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        Some(BoundingBox::from_points(&[self.v1, self.v2, self.v3]))
+    }
+
     fn material(&self) -> &Material {
         &self.material
     }
 
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        if !self.material.is_emissive() {
+            return None;
+        }
+        Some(Box::new(MeshLight::new(
+            self.material.emitted(),
+            vec![[self.v1, self.v2, self.v3]],
+            samples,
+        )))
+    }
+
     fn info(&self) -> String {
         format!("Triangle: {:?} {:?} {:?}", self.v1, self.v2, self.v3)
     }
 }
 
-fn same_side(p1: Vector3, p2: Vector3, a: Vector3, b: Vector3) -> bool {
-    let cp1 = (b - a).cross(&(p1 - a));
-    let cp2 = (b - a).cross(&(p2 - a));
-    cp1.dot(&cp2) >= 0.0
+/// Intersect a ray with a triangle (Möller–Trumbore), interpolating the
+/// vertex normals at the hit point. Shared by `Triangle` and `TriangleMesh`.
+///
+/// The texture coordinates are the barycentric weights of the second and
+/// third vertices; use `with_texcoords` to interpolate actual coordinates.
+pub fn intersect_triangle(
+    ray: &Ray,
+    vertices: [Vector3; 3],
+    normals: [Vector3; 3],
+) -> Option<Intersection> {
+    let [v1, v2, v3] = vertices;
+    let edge1 = v2 - v1;
+    let edge2 = v3 - v1;
+    let p = ray.direction.cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < PARALLEL_TOLERANCE {
+        // Ray is parallel to the triangle's plane
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let start_to_v1 = ray.start - v1;
+    let u = start_to_v1.dot(&p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = start_to_v1.cross(&edge1);
+    let v = ray.direction.dot(&q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inverse_determinant;
+    if t < EPSILON {
+        return None;
+    }
+    let point = ray.eval(t)?;
+    let barycentric = [1.0 - u - v, u, v];
+    let [n1, n2, n3] = normals;
+    let normal =
+        (n1 * barycentric[0] + n2 * barycentric[1] + n3 * barycentric[2])
+            .normalized();
+    // Interpolated normals can tip over near the silhouette, so the side
+    // the vertex normals agree on is taken as outside
+    let face_normal = edge1.cross(&edge2).normalized();
+    let face_normal = if face_normal.dot(&(n1 + n2 + n3)) < 0.0 {
+        -face_normal
+    } else {
+        face_normal
+    };
+    Some(
+        Intersection::new(normal, point)
+            .with_face_normal(face_normal)
+            .with_barycentric(barycentric)
+            .with_uv((u, v)),
+    )
+}
+
+/// Set the texture coordinates of a triangle intersection by interpolating
+/// the ones at the vertices
+pub fn with_texcoords(
+    intersection: Intersection,
+    texcoords: [(f64, f64); 3],
+) -> Intersection {
+    let weights = intersection.barycentric.unwrap_or([1.0, 0.0, 0.0]);
+    let mut uv = (0.0, 0.0);
+    for (weight, texcoord) in weights.iter().zip(&texcoords) {
+        uv.0 += weight * texcoord.0;
+        uv.1 += weight * texcoord.1;
+    }
+    intersection.with_uv(uv)
 }
 
-fn bary_interp(
-    point: Vector3,
-    p1: Vector3,
-    p2: Vector3,
-    p3: Vector3,
-    n1: Vector3,
-    n2: Vector3,
-    n3: Vector3,
+/// A normal for the triangle's plane, facing towards the camera
+pub fn guess_normal(
+    v1: Vector3,
+    v2: Vector3,
+    v3: Vector3,
+    camera: &Camera,
 ) -> Vector3 {
-    let d1 = (point - p1).length();
-    let d2 = (point - p2).length();
-    let d3 = (point - p3).length();
-    let total = (d1 * d1 + d2 * d2 + d3 * d3).sqrt();
-    let d1 = d1 / total;
-    let d2 = d2 / total;
-    let d3 = d3 / total;
-    (n1 * d1 + n2 * d2 + n3 * d3).normalized()
+    let normal = (v1 - v2).cross(&(v3 - v2)).normalized();
+    if normal.dot(&camera.direction) < 0.0 {
+        normal
+    } else {
+        -normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERTICES: [Vector3; 3] = [
+        Vector3 {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Vector3 {
+            x: 2.0,
+            y: 0.0,
+            z: 0.0,
+        },
+        Vector3 {
+            x: 0.0,
+            y: 2.0,
+            z: 0.0,
+        },
+    ];
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn barycentric_coordinates_of_hit_point() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        // Straight down onto (0.5, 1.0, 0), and at an angle onto the same point
+        let rays = [
+            Ray::new(Vector3::new(0.5, 1.0, 3.0), Vector3::new(0.0, 0.0, -1.0)),
+            Ray::new(
+                Vector3::new(2.5, 3.0, 2.0),
+                Vector3::new(-1.0, -1.0, -1.0),
+            ),
+        ];
+        for ray in &rays {
+            let hit = intersect_triangle(ray, VERTICES, [normal; 3]).unwrap();
+            assert_close(hit.point.x, 0.5);
+            assert_close(hit.point.y, 1.0);
+            assert_close(hit.point.z, 0.0);
+            // point = 0.25 * v1 + 0.25 * v2 + 0.5 * v3
+            let [b1, b2, b3] = hit.barycentric.unwrap();
+            assert_close(b1, 0.25);
+            assert_close(b2, 0.25);
+            assert_close(b3, 0.5);
+        }
+    }
+
+    #[test]
+    fn normals_are_interpolated() {
+        let normals = [
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        // At the midpoint of the v2-v3 edge only their normals contribute
+        let ray =
+            Ray::new(Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+        let hit = intersect_triangle(&ray, VERTICES, normals).unwrap();
+        let expected = 0.5f64.sqrt();
+        assert_close(hit.surface_normal.x, expected);
+        assert_close(hit.surface_normal.y, expected);
+        assert_close(hit.surface_normal.z, 0.0);
+    }
+
+    #[test]
+    fn texcoords_are_interpolated() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let triangle = Triangle::new(
+            Material::default(),
+            VERTICES[0],
+            VERTICES[1],
+            VERTICES[2],
+            normal,
+            normal,
+            normal,
+        )
+        .with_texcoords([(0.0, 0.0), (1.0, 0.0), (0.0, 0.5)]);
+        let ray =
+            Ray::new(Vector3::new(0.5, 1.0, 3.0), Vector3::new(0.0, 0.0, -1.0));
+        let (u, v) = triangle.intersects(&ray).unwrap().uv;
+        assert_close(u, 0.25);
+        assert_close(v, 0.25);
+    }
+
+    #[test]
+    fn misses_outside_and_behind() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let down = Vector3::new(0.0, 0.0, -1.0);
+        // Just past the hypotenuse
+        let outside = Ray::new(Vector3::new(1.01, 1.0, 1.0), down);
+        assert!(intersect_triangle(&outside, VERTICES, [normal; 3]).is_none());
+        // Triangle is behind the ray
+        let behind = Ray::new(Vector3::new(0.5, 0.5, -1.0), down);
+        assert!(intersect_triangle(&behind, VERTICES, [normal; 3]).is_none());
+        // Parallel to the triangle
+        let parallel =
+            Ray::new(Vector3::new(-1.0, 0.5, 0.0), Vector3::new(1.0, 0.0, 0.0));
+        assert!(intersect_triangle(&parallel, VERTICES, [normal; 3]).is_none());
+    }
 }
@@ -0,0 +1,340 @@
+//! A mesh of triangles sharing vertex data and a material
+//!
+//! Much lighter than a `Triangle` per face: vertex positions, normals and
+//! texture coordinates are stored once and faces refer to them by index.
+//! The faces have their own BVH, so the scene's BVH only sees one object.
+
+use std::collections::HashMap;
+
+use crate::bounding_box::BoundingBox;
+use crate::bvh::Bvh;
+use crate::intersection::Intersection;
+use crate::lights::light::Light;
+use crate::lights::mesh_light::MeshLight;
+use crate::material::Material;
+use crate::obj::{ObjMesh, ObjVertex};
+use crate::objects::object::Object;
+use crate::objects::triangle::{intersect_triangle, with_texcoords};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// A single triangle of a mesh, as indices into the mesh's buffers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    /// Per-vertex normals; if missing the face is flat shaded, facing the
+    /// side from which the vertices appear counter-clockwise
+    pub normals: Option<[usize; 3]>,
+    pub texcoords: Option<[usize; 3]>,
+}
+
+pub struct TriangleMesh {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub texcoords: Vec<(f64, f64)>,
+    pub faces: Vec<MeshFace>,
+    pub material: Material,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    /// Create a mesh; all face indices must be in range of the buffers
+    pub fn new(
+        material: Material,
+        positions: Vec<Vector3>,
+        normals: Vec<Vector3>,
+        texcoords: Vec<(f64, f64)>,
+        faces: Vec<MeshFace>,
+    ) -> Self {
+        let face_bounds: Vec<_> = faces
+            .iter()
+            .enumerate()
+            .map(|(index, face)| {
+                let [a, b, c] = face.positions;
+                (
+                    index,
+                    BoundingBox::from_points(&[
+                        positions[a],
+                        positions[b],
+                        positions[c],
+                    ]),
+                )
+            })
+            .collect();
+        Self {
+            positions,
+            normals,
+            texcoords,
+            bvh: Bvh::build(&face_bounds),
+            faces,
+            material,
+        }
+    }
+
+    /// Create a mesh from a parsed OBJ file
+    pub fn from_obj(material: Material, obj: ObjMesh) -> Self {
+        let faces = obj
+            .triangles
+            .iter()
+            .map(|corners| MeshFace {
+                positions: [
+                    corners[0].position,
+                    corners[1].position,
+                    corners[2].position,
+                ],
+                normals: all_corners(corners, |corner| corner.normal),
+                texcoords: all_corners(corners, |corner| corner.texcoord),
+            })
+            .collect();
+        Self::new(material, obj.positions, obj.normals, obj.texcoords, faces)
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    fn face_vertices(&self, face: &MeshFace) -> [Vector3; 3] {
+        let [a, b, c] = face.positions;
+        [self.positions[a], self.positions[b], self.positions[c]]
+    }
+
+    fn face_normals(&self, face: &MeshFace) -> [Vector3; 3] {
+        match face.normals {
+            Some([a, b, c]) => {
+                [self.normals[a], self.normals[b], self.normals[c]]
+            }
+            None => {
+                let [v1, v2, v3] = self.face_vertices(face);
+                let normal = (v2 - v1).cross(&(v3 - v1)).normalized();
+                [normal, normal, normal]
+            }
+        }
+    }
+
+    fn intersect_face(&self, index: usize, ray: &Ray) -> Option<Intersection> {
+        let face = &self.faces[index];
+        let intersection = intersect_triangle(
+            ray,
+            self.face_vertices(face),
+            self.face_normals(face),
+        )?;
+        match face.texcoords {
+            Some([a, b, c]) => Some(with_texcoords(
+                intersection,
+                [self.texcoords[a], self.texcoords[b], self.texcoords[c]],
+            )),
+            None => Some(intersection),
+        }
+    }
+}
+
+impl Object for TriangleMesh {
+    fn intersects(&self, ray: &Ray) -> Option<Intersection> {
+        self.bvh
+            .closest(ray, |index, ray| self.intersect_face(index, ray))
+            .map(|(_, intersection)| intersection)
+    }
+
+    fn bounding_box(&self) -> Option<BoundingBox> {
+        self.bvh.bounds()
+    }
+
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn light(&self, samples: usize) -> Option<Box<Light>> {
+        if !self.material.is_emissive() || self.faces.is_empty() {
+            return None;
+        }
+        let triangles = self
+            .faces
+            .iter()
+            .map(|face| self.face_vertices(face))
+            .collect();
+        Some(Box::new(MeshLight::new(
+            self.material.emitted(),
+            triangles,
+            samples,
+        )))
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "TriangleMesh: {} faces, {} positions, {} normals, {} texcoords",
+            self.faces.len(),
+            self.positions.len(),
+            self.normals.len(),
+            self.texcoords.len()
+        )
+    }
+}
+
+/// Collects faces that refer to a larger, shared list of vertices (like the
+/// `vertex`/`normal` lists of a scene file), then builds a mesh containing
+/// only the vertices that are actually used
+#[derive(Debug, Default)]
+pub struct TriangleMeshBuilder {
+    positions: Vec<Vector3>,
+    normals: Vec<Vector3>,
+    texcoords: Vec<(f64, f64)>,
+    faces: Vec<MeshFace>,
+    position_indices: HashMap<usize, usize>,
+    normal_indices: HashMap<usize, usize>,
+    texcoord_indices: HashMap<usize, usize>,
+}
+
+impl TriangleMeshBuilder {
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    /// Add a face from the shared lists; `normals` are indices into
+    /// `all_normals`
+    pub fn add_face(
+        &mut self,
+        all_positions: &[Vector3],
+        all_normals: &[Vector3],
+        positions: [usize; 3],
+        normals: [usize; 3],
+    ) {
+        let mut face_positions = [0; 3];
+        let mut face_normals = [0; 3];
+        for i in 0..3 {
+            face_positions[i] = remap(
+                &mut self.position_indices,
+                &mut self.positions,
+                all_positions,
+                positions[i],
+            );
+            face_normals[i] = remap(
+                &mut self.normal_indices,
+                &mut self.normals,
+                all_normals,
+                normals[i],
+            );
+        }
+        self.faces.push(MeshFace {
+            positions: face_positions,
+            normals: Some(face_normals),
+            texcoords: None,
+        });
+    }
+
+    /// Add a face with the same normal at every corner
+    pub fn add_flat_face(
+        &mut self,
+        all_positions: &[Vector3],
+        positions: [usize; 3],
+        normal: Vector3,
+    ) {
+        let mut face_positions = [0; 3];
+        for i in 0..3 {
+            face_positions[i] = remap(
+                &mut self.position_indices,
+                &mut self.positions,
+                all_positions,
+                positions[i],
+            );
+        }
+        let normal_index = self.normals.len();
+        self.normals.push(normal);
+        self.faces.push(MeshFace {
+            positions: face_positions,
+            normals: Some([normal_index; 3]),
+            texcoords: None,
+        });
+    }
+
+    /// Give the most recently added face texture coordinates from the
+    /// shared list
+    pub fn add_texcoords(
+        &mut self,
+        all_texcoords: &[(f64, f64)],
+        texcoords: [usize; 3],
+    ) {
+        let mut face_texcoords = [0; 3];
+        for i in 0..3 {
+            face_texcoords[i] = remap(
+                &mut self.texcoord_indices,
+                &mut self.texcoords,
+                all_texcoords,
+                texcoords[i],
+            );
+        }
+        if let Some(face) = self.faces.last_mut() {
+            face.texcoords = Some(face_texcoords);
+        }
+    }
+
+    /// Build the mesh, leaving the builder empty
+    pub fn build(&mut self, material: Material) -> TriangleMesh {
+        let builder = ::std::mem::take(self);
+        TriangleMesh::new(
+            material,
+            builder.positions,
+            builder.normals,
+            builder.texcoords,
+            builder.faces,
+        )
+    }
+}
+
+/// Indices for all three corners of an OBJ face, if every corner has one
+fn all_corners<F>(corners: &[ObjVertex; 3], index: F) -> Option<[usize; 3]>
+where
+    F: Fn(&ObjVertex) -> Option<usize>,
+{
+    match (index(&corners[0]), index(&corners[1]), index(&corners[2])) {
+        (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+        _ => None,
+    }
+}
+
+/// Index of `shared[index]` in `local`, copying it over the first time
+fn remap<T: Copy>(
+    indices: &mut HashMap<usize, usize>,
+    local: &mut Vec<T>,
+    shared: &[T],
+    index: usize,
+) -> usize {
+    *indices.entry(index).or_insert_with(|| {
+        local.push(shared[index]);
+        local.len() - 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_keeps_only_used_vertices() {
+        let shared = [
+            Vector3::new(9.0, 9.0, 9.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+        ];
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let mut builder = TriangleMeshBuilder::default();
+        builder.add_flat_face(&shared, [1, 2, 3], normal);
+        builder.add_flat_face(&shared, [2, 4, 3], normal);
+        let mesh = builder.build(Material::default());
+        assert!(builder.is_empty());
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.faces[1].positions, [1, 3, 2]);
+
+        let ray = Ray::new(
+            Vector3::new(0.75, 0.75, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        let hit = mesh.intersects(&ray).unwrap();
+        assert_eq!(hit.surface_normal, normal);
+    }
+}
@@ -0,0 +1,274 @@
+//! Monte Carlo path tracing
+//!
+//! Unlike the Whitted-style `RayTracer`, light also bounces off diffuse
+//! surfaces, so scenes get indirect lighting and color bleeding. A path
+//! takes one bounce per hit: a cosine-weighted diffuse bounce, a mirror
+//! reflection or a refraction, chosen in proportion to the material's
+//! colors. Lights are sampled directly at every hit (next event estimation)
+//! and Russian roulette ends paths that carry little light.
+//!
+//! Glowing objects that stand in as lights are only picked up by a path
+//! where sampling the lights couldn't have found them: seen from the
+//! camera, or in a mirror or through glass. Those with no light of their
+//! own (e.g. glowing planes) are picked up after every bounce.
+//!
+//! Lights keep the `RayTracer`'s conventions, so a scene is about as bright
+//! with either integrator. The ambient light is ignored: indirect lighting
+//! replaces it.
+
+use crate::intersection::Intersection;
+use crate::material::Material;
+use crate::pixel::Pixel;
+use crate::random::Rng;
+use crate::ray::Ray;
+use crate::ray_tracer::{cross_surface, refraction};
+use crate::sampler;
+use crate::scene::Scene;
+
+/// Bounces after which Russian roulette may end a path
+const ROULETTE_DEPTH: usize = 3;
+
+/// Highest chance of a path surviving Russian roulette, so paths between
+/// white surfaces still end
+const MAX_SURVIVAL: f64 = 0.95;
+
+pub struct PathTracer;
+
+impl PathTracer {
+    /// Estimate the light arriving along a ray. Bounces stop at the scene's
+    /// `max_depth`.
+    pub fn radiance(&self, scene: &Scene, ray: &Ray, rng: &mut Rng) -> Pixel {
+        let mut color = Pixel::from_rgba_unclamped(0.0, 0.0, 0.0, 1.0);
+        // How much of the light found further along the path reaches the
+        // camera
+        let mut throughput = Pixel::from_rgba_unclamped(1.0, 1.0, 1.0, 1.0);
+        let mut ray = *ray;
+        // Materials of the objects the path is inside of, innermost last
+        let mut media: Vec<&Material> = Vec::new();
+        // Whether the last bounce was one that lights aren't sampled for
+        let mut specular_bounce = true;
+
+        for depth in 0..=scene.max_depth {
+            let (index, intersection) = match scene.closest_hit(&ray) {
+                Some(hit) => hit,
+                None => {
+                    // An environment light has already been sampled
+                    if specular_bounce || !scene.environment_light {
+                        color = color
+                            + throughput
+                                * scene.background_color(ray.direction);
+                    }
+                    break;
+                }
+            };
+            if let Some(medium) = media.last() {
+                let distance = (intersection.point - ray.start).length();
+                throughput = throughput * medium.transmittance(distance);
+            }
+            let object = scene.objects[index].as_ref();
+            let material = object.material().at(&intersection);
+            let is_light = scene.light_objects.get(index) == Some(&true);
+            if specular_bounce || !is_light {
+                color = color + throughput * material.emitted();
+            }
+
+            // Diffuse shading uses the side of the surface the ray hit
+            let normal = intersection.surface_normal;
+            let facing = Intersection::new(
+                if ray.direction.dot(&normal) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                },
+                intersection.point,
+            );
+            color = color
+                + throughput
+                    * direct_light(scene, &facing, &material, depth == 0, rng);
+
+            // Pick one way for the path to continue
+            let (refracted, reflectance) =
+                refraction(&ray, &intersection, &material);
+            let lobes = [
+                material.diffuse,
+                material.specular + material.transmissive * reflectance,
+                match refracted {
+                    Some(_) => material.transmissive * (1.0 - reflectance),
+                    None => Pixel::from_rgb(0.0, 0.0, 0.0),
+                },
+            ];
+            let weights: Vec<_> = lobes.iter().map(average).collect();
+            let total: f64 = weights.iter().sum();
+            if total <= 0.0 {
+                break;
+            }
+            let mut choice = rng.next_f64() * total;
+            let lobe = weights
+                .iter()
+                .position(|&weight| {
+                    choice -= weight;
+                    choice < 0.0
+                })
+                // Rounding can leave a little over at the end
+                .or_else(|| weights.iter().rposition(|&weight| weight > 0.0))
+                .unwrap_or(0);
+            throughput = throughput * lobes[lobe] * (total / weights[lobe]);
+            specular_bounce = lobe != 0;
+
+            ray = match (lobe, refracted) {
+                (0, _) => Ray::new(
+                    intersection.point,
+                    sampler::cosine_hemisphere(
+                        facing.surface_normal,
+                        (rng.next_f64(), rng.next_f64()),
+                    ),
+                ),
+                (2, Some(refracted)) => {
+                    cross_surface(
+                        &mut media,
+                        object.material(),
+                        &ray,
+                        &intersection,
+                    );
+                    refracted
+                }
+                _ => ray.reflect(intersection.point, normal),
+            };
+
+            if depth >= ROULETTE_DEPTH {
+                let survival = throughput
+                    .r
+                    .max(throughput.g)
+                    .max(throughput.b)
+                    .min(MAX_SURVIVAL);
+                if rng.next_f64() >= survival {
+                    break;
+                }
+                throughput = throughput * (1.0 / survival);
+            }
+        }
+
+        color
+    }
+}
+
+/// Light reaching a point straight from the scene's lights, one sample per
+/// light. Phong highlights depend on where they're seen from, so they're
+/// only added where the camera is the viewer.
+fn direct_light(
+    scene: &Scene,
+    intersection: &Intersection,
+    material: &Material,
+    seen_by_camera: bool,
+    rng: &mut Rng,
+) -> Pixel {
+    let mut sum = Pixel::from_rgba_unclamped(0.0, 0.0, 0.0, 1.0);
+    for light in &scene.lights {
+        let sample =
+            light.sample(intersection, (rng.next_f64(), rng.next_f64()));
+        let in_shadow = scene.any_intersection(&Ray::with_t_max(
+            intersection.point,
+            sample.direction,
+            sample.distance,
+        ));
+        if in_shadow {
+            continue;
+        }
+        sum = sum + light.diffuse(intersection, material, &sample);
+        if seen_by_camera {
+            sum = sum
+                + light.specular(
+                    &scene.camera,
+                    intersection,
+                    material,
+                    &sample,
+                );
+        }
+    }
+    sum
+}
+
+fn average(color: &Pixel) -> f64 {
+    (color.r + color.g + color.b) / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Vector3;
+
+    #[test]
+    fn sky_lights_a_diffuse_floor() {
+        let scene = Scene::from_text(String::from(
+            "background 1 1 1\n\
+             integrator path\n\
+             material 0 0 0 .5 .5 .5 0 0 0 1 0 0 0 1\n\
+             plane 0 0 0 0 1 0\n",
+        ))
+        .unwrap();
+        let ray =
+            Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let mut rng = Rng::new(1);
+        let samples = 2000;
+        let mut mean = 0.0;
+        for _ in 0..samples {
+            mean +=
+                PathTracer.radiance(&scene, &ray, &mut rng).g / samples as f64;
+        }
+        // The floor sees only sky, and reflects half of it
+        assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+    }
+
+    #[test]
+    fn glowing_sphere_lights_the_floor() {
+        let scene = Scene::from_text(String::from(
+            "integrator path\n\
+             material 0 0 0 .5 .5 .5 0 0 0 1 0 0 0 1\n\
+             plane 0 0 0 0 1 0\n\
+             material 0 0 0 0 0 0 0 0 0 1 0 0 0 1\n\
+             emission 1 1 1\n\
+             sphere 0 2 0 .5\n",
+        ))
+        .unwrap();
+        assert_eq!(scene.lights.len(), 1);
+        let ray =
+            Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let mut rng = Rng::new(2);
+        let samples = 4000;
+        let mut mean = 0.0;
+        for _ in 0..samples {
+            mean +=
+                PathTracer.radiance(&scene, &ray, &mut rng).g / samples as f64;
+        }
+        // A sphere of radius r at height h overhead gives an irradiance of
+        // π (r / h)^2 times its radiance
+        let expected = 0.5 * (0.5f64 / 2.0).powi(2);
+        assert!((mean - expected).abs() < 0.02 * expected, "{}", mean);
+    }
+
+    #[test]
+    fn glowing_plane_lights_the_floor() {
+        // Planes are endless, so they have no light standing in for them
+        let scene = Scene::from_text(String::from(
+            "integrator path\n\
+             material 0 0 0 .5 .5 .5 0 0 0 1 0 0 0 1\n\
+             plane 0 0 0 0 1 0\n\
+             material 0 0 0 0 0 0 0 0 0 1 0 0 0 1\n\
+             emission 1 1 1\n\
+             plane 0 2 0 0 -1 0\n",
+        ))
+        .unwrap();
+        assert!(scene.lights.is_empty());
+        let ray =
+            Ray::new(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+        let mut rng = Rng::new(3);
+        let samples = 2000;
+        let mut mean = 0.0;
+        for _ in 0..samples {
+            mean +=
+                PathTracer.radiance(&scene, &ray, &mut rng).g / samples as f64;
+        }
+        // The floor sees only the glowing ceiling, and reflects half of it
+        assert!((mean - 0.5).abs() < 0.02, "{}", mean);
+    }
+}
@@ -0,0 +1,133 @@
+//! Real roots of polynomials, for surfaces like the torus whose
+//! intersections can't be found with the quadratic formula
+//!
+//! Roots are isolated with the roots of the derivative: between two of
+//! those, the polynomial only goes up or only goes down, so it crosses 0 at
+//! most once and bisection finds the crossing. This is slower than solving
+//! a quartic with a formula, but doesn't fall apart from rounding.
+
+use std::iter;
+
+/// Steps of bisection per root; enough to get down to rounding error from
+/// any starting interval
+const BISECTION_STEPS: usize = 80;
+
+/// The real roots of the polynomial with `coefficients`, lowest power
+/// first, in increasing order. Roots where the polynomial only touches 0
+/// without crossing it can be missed.
+pub fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    // Drop leading coefficients that are 0 (or as good as), lowering the
+    // degree
+    let largest = coefficients.iter().fold(0.0_f64, |largest, coefficient| {
+        largest.max(coefficient.abs())
+    });
+    let degree = match coefficients
+        .iter()
+        .rposition(|coefficient| coefficient.abs() > largest * 1e-12)
+    {
+        Some(degree) => degree,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[..=degree];
+
+    match degree {
+        0 => Vec::new(),
+        1 => vec![-coefficients[0] / coefficients[1]],
+        2 => quadratic_roots(coefficients[2], coefficients[1], coefficients[0]),
+        _ => {
+            let derivative: Vec<f64> = coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(power, coefficient)| coefficient * power as f64)
+                .collect();
+            // Every root lies within this distance of 0 (Cauchy's bound)
+            let bound = 1.0
+                + coefficients[..degree].iter().fold(0.0_f64, |bound, c| {
+                    bound.max((c / coefficients[degree]).abs())
+                });
+            let turning_points = real_roots(&derivative)
+                .into_iter()
+                .filter(|point| point.abs() < bound);
+
+            let mut roots = Vec::new();
+            let mut previous = (-bound, evaluate(coefficients, -bound));
+            for point in turning_points.chain(iter::once(bound)) {
+                let value = evaluate(coefficients, point);
+                if value == 0.0 {
+                    roots.push(point);
+                } else if previous.1 != 0.0
+                    && (previous.1 < 0.0) != (value < 0.0)
+                {
+                    roots.push(bisect(coefficients, previous.0, point));
+                }
+                previous = (point, value);
+            }
+            roots
+        }
+    }
+}
+
+/// Real roots of `a x^2 + b x + c`, in increasing order
+pub fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    // Avoids subtracting nearly equal numbers, which loses precision
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        // Both b and c are 0
+        return vec![0.0];
+    }
+    let (first, second) = (q / a, c / q);
+    vec![first.min(second), first.max(second)]
+}
+
+/// Value of the polynomial at `x`, by Horner's method
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |value, coefficient| value * x + coefficient)
+}
+
+/// The root between `low` and `high`, where the polynomial has opposite
+/// signs
+fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> f64 {
+    let low_negative = evaluate(coefficients, low) < 0.0;
+    for _ in 0..BISECTION_STEPS {
+        let middle = (low + high) / 2.0;
+        if middle <= low || middle >= high {
+            break;
+        }
+        if (evaluate(coefficients, middle) < 0.0) == low_negative {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_all_real_roots() {
+        // (x + 3)(x - 0.5)(x - 1)(x - 200) multiplied out
+        let coefficients = [-300.0, 801.5, -304.0, -198.5, 1.0];
+        let roots = real_roots(&coefficients);
+        let expected = [-3.0, 0.5, 1.0, 200.0];
+        assert_eq!(roots.len(), expected.len());
+        for (root, expected) in roots.iter().zip(expected.iter()) {
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+
+        // x^4 + 1 never crosses 0
+        assert!(real_roots(&[1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+        // A leading 0 makes it a quadratic
+        assert_eq!(real_roots(&[-4.0, 0.0, 1.0, 0.0]), vec![-2.0, 2.0]);
+    }
+}
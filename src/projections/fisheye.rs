@@ -0,0 +1,69 @@
+//! Equidistant fisheye projection. The angle between a ray and the camera's
+//! direction grows evenly with distance from the center of the image, out
+//! to a circle touching the top and bottom edges.
+
+use crate::camera::Camera;
+use crate::projections::projection::{film_position, Projection};
+use crate::ray::Ray;
+
+#[derive(Debug)]
+pub struct Fisheye {
+    /// Angle across the circle, in radians, up to 2π for looking all the
+    /// way around
+    pub field_of_view: f64,
+}
+
+impl Projection for Fisheye {
+    fn primary_ray(
+        &self,
+        camera: &Camera,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray> {
+        let (x, y) = film_position(resolution, row, col);
+        let x = x * resolution.0 as f64 / resolution.1 as f64;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let angle = radius * self.field_of_view / 2.0;
+        // Straight ahead in the center, where the direction around it is
+        // undefined
+        let (x, y) = if radius > 0.0 {
+            (x / radius, y / radius)
+        } else {
+            (0.0, 0.0)
+        };
+        let direction = camera.direction * angle.cos()
+            + (camera.right * x + camera.up * y) * angle.sin();
+        Some(Ray::new(camera.position, direction))
+    }
+
+    fn info(&self) -> String {
+        format!("Fisheye {}", self.field_of_view.to_degrees())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angle_grows_evenly_to_the_circle() {
+        let camera = Camera::default();
+        let fisheye = Fisheye {
+            field_of_view: 180.0_f64.to_radians(),
+        };
+        // 5x3 pixels, so the middle row crosses the center of the circle
+        let angle = |col: f64| {
+            let ray = fisheye.primary_ray(&camera, (5, 3), 1.0, col).unwrap();
+            ray.direction.dot(&camera.direction).acos().to_degrees()
+        };
+        assert!(angle(2.0).abs() < 1e-9);
+        assert!((angle(2.5) - 30.0).abs() < 1e-9);
+        assert!((angle(3.2) - 72.0).abs() < 1e-9);
+        // Past the edge of the circle
+        assert!(fisheye.primary_ray(&camera, (5, 3), 1.0, 3.6).is_none());
+    }
+}
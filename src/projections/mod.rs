@@ -0,0 +1,6 @@
+//! Ways a camera can map the film to rays
+pub mod fisheye;
+pub mod orthographic;
+pub mod panorama;
+pub mod perspective;
+pub mod projection;
@@ -0,0 +1,41 @@
+//! Orthographic projection. All rays travel in the camera's direction, from
+//! points spread across a rectangle, so things don't shrink with distance.
+
+use crate::camera::Camera;
+use crate::projections::projection::{film_position, Projection};
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+#[derive(Debug)]
+pub struct Orthographic {
+    /// Height of the rectangle the rays start from, in world units. Its
+    /// width follows from the image's aspect ratio.
+    pub height: f64,
+}
+
+impl Projection for Orthographic {
+    fn primary_ray(
+        &self,
+        camera: &Camera,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray> {
+        let (x, y) = film_position(resolution, row, col);
+        let aspect = resolution.0 as f64 / resolution.1 as f64;
+        let half_height = self.height / 2.0;
+        let start = camera.position
+            + camera.right * (x * aspect * half_height)
+            + camera.up * (y * half_height);
+        Some(Ray::new(start, camera.direction))
+    }
+
+    /// In focus on the plane `focal_distance` in front of the camera
+    fn focus(&self, camera: &Camera, ray: &Ray) -> Vector3 {
+        ray.start + ray.direction * camera.focal_distance
+    }
+
+    fn info(&self) -> String {
+        format!("Orthographic {}", self.height)
+    }
+}
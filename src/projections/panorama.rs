@@ -0,0 +1,35 @@
+//! Equirectangular panorama. Longitude goes across the image, all the way
+//! around the camera, and latitude down it, from straight up to straight
+//! down. Looks right with a 2:1 aspect ratio.
+
+use std::f64::consts::PI;
+
+use crate::camera::Camera;
+use crate::projections::projection::{film_position, Projection};
+use crate::ray::Ray;
+
+/// The camera's direction is in the center of the image, and its up vector
+/// at the top
+#[derive(Debug)]
+pub struct Panorama;
+
+impl Projection for Panorama {
+    fn primary_ray(
+        &self,
+        camera: &Camera,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray> {
+        let (x, y) = film_position(resolution, row, col);
+        let (longitude, latitude) = (x * PI, y * PI / 2.0);
+        let around =
+            camera.direction * longitude.cos() + camera.right * longitude.sin();
+        let direction = around * latitude.cos() + camera.up * latitude.sin();
+        Some(Ray::new(camera.position, direction))
+    }
+
+    fn info(&self) -> String {
+        String::from("Panorama")
+    }
+}
@@ -0,0 +1,52 @@
+//! Perspective projection. Rays spread out from the camera's position
+//! through an image plane in front of it, like a pinhole camera.
+
+use crate::camera::Camera;
+use crate::projections::projection::Projection;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Uses the camera's `vert_half_angle` as the field of view
+#[derive(Debug)]
+pub struct Perspective;
+
+impl Projection for Perspective {
+    fn primary_ray(
+        &self,
+        camera: &Camera,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray> {
+        let viewport_height = 2.0 * camera.vert_half_angle.tan();
+        let viewport_width =
+            viewport_height * (resolution.0 as f64 / resolution.1 as f64);
+        let pixel_width = viewport_width / resolution.0 as f64;
+        let pixel_height = viewport_height / resolution.1 as f64;
+
+        // The upper-left-most pixel
+        // 0.5s are to center the rays on each pixel
+        let upper_left = camera.position
+            + camera.direction
+            + camera.up * pixel_height * (resolution.1 as f64 / 2.0 - 0.5)
+            - camera.right * pixel_width * (resolution.0 as f64 / 2.0 - 0.5);
+
+        let image_plane_location = upper_left - camera.up * pixel_height * row
+            + camera.right * pixel_width * col;
+        Some(Ray::new(
+            camera.position,
+            image_plane_location - camera.position,
+        ))
+    }
+
+    /// In focus on the plane `focal_distance` in front of the camera
+    fn focus(&self, camera: &Camera, ray: &Ray) -> Vector3 {
+        ray.start
+            + ray.direction
+                * (camera.focal_distance / ray.direction.dot(&camera.direction))
+    }
+
+    fn info(&self) -> String {
+        String::from("Perspective")
+    }
+}
@@ -0,0 +1,56 @@
+//! A generic projection
+//!
+//! Turns a point on the film into the ray that lands there. The camera
+//! supplies the position and orientation; the projection decides how rays
+//! spread out from it.
+
+use std::fmt;
+
+use crate::camera::Camera;
+use crate::ray::Ray;
+use crate::vector::Vector3;
+
+/// Projections are shared between render threads, so they must be `Send`
+/// and `Sync`
+pub trait Projection: Send + Sync {
+    /// The ray through a point on the film, or `None` if nothing is seen
+    /// there (e.g. outside a fisheye's circle)
+    ///
+    /// `row` and `col` are pixel coordinates, where whole numbers are the
+    /// centers of pixels.
+    fn primary_ray(
+        &self,
+        camera: &Camera,
+        resolution: (usize, usize),
+        row: f64,
+        col: f64,
+    ) -> Option<Ray>;
+
+    /// The point a pinhole ray from `primary_ray` is in focus at, which
+    /// rays from the rest of a thin lens pass through. By default, the
+    /// point `focal_distance` along the ray.
+    fn focus(&self, camera: &Camera, ray: &Ray) -> Vector3 {
+        ray.start + ray.direction.normalized() * camera.focal_distance
+    }
+
+    fn info(&self) -> String;
+}
+
+impl fmt::Debug for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.info())
+    }
+}
+
+/// Where a point is on the film, from -1 to 1 left to right and bottom to
+/// top
+pub fn film_position(
+    resolution: (usize, usize),
+    row: f64,
+    col: f64,
+) -> (f64, f64) {
+    (
+        (col + 0.5) / resolution.0 as f64 * 2.0 - 1.0,
+        1.0 - (row + 0.5) / resolution.1 as f64 * 2.0,
+    )
+}
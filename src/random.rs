@@ -0,0 +1,42 @@
+//! A small, seedable pseudo-random number generator
+//!
+//! Every pixel gets its own generator seeded from its coordinates, so a
+//! render is reproducible no matter what order the pixels are traced in.
+
+/// PCG32 random number generator (see <http://www.pcg-random.org>)
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+const INCREMENT: u64 = 1_442_695_040_888_963_407;
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    /// A generator for a particular pixel of the image
+    pub fn for_pixel(row: usize, col: usize) -> Self {
+        Self::new(((row as u64) << 32) ^ (col as u64))
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state.wrapping_mul(MULTIPLIER).wrapping_add(INCREMENT);
+        let xorshifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xorshifted.rotate_right(rotation)
+    }
+
+    /// Uniformly distributed number in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        // 2^32, so the result never reaches 1
+        f64::from(self.next_u32()) / 4_294_967_296.0
+    }
+}
@@ -2,7 +2,7 @@
 
 use crate::vector::Vector3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub start: Vector3,
     pub direction: Vector3,
@@ -37,12 +37,14 @@ impl Ray {
         Self::new(new_start, self.direction.reflect(&normal).normalized())
     }
 
+    /// The refracted ray, or `None` for total internal reflection
     pub fn refract(
         &self,
         new_start: Vector3,
         normal: Vector3,
         ior: f64,
-    ) -> Self {
-        Self::new(new_start, self.direction.refract(normal, ior).normalized())
+    ) -> Option<Self> {
+        let direction = self.direction.refract(normal, ior)?;
+        Some(Self::new(new_start, direction))
     }
 }
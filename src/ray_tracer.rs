@@ -1,147 +1,384 @@
 //! The main ray tracing implementation
 
+use std::ptr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
 use crate::image::Image;
 use crate::intersection::Intersection;
+use crate::material::Material;
 use crate::objects::object::Object;
+use crate::path_tracer::PathTracer;
 use crate::pixel::Pixel;
+use crate::random::Rng;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::scene::Scene;
-use crate::vector::MAX_VECTOR3;
+
+/// Width and height of the square tiles the image is split into
+const TILE_SIZE: usize = 32;
+
+/// Log progress every time this many more pixels are done
+const PROGRESS_INTERVAL: usize = 10000;
+
+/// A rectangular region of the image
+#[derive(Debug, Clone, Copy)]
+struct Tile {
+    row_start: usize,
+    row_end: usize,
+    col_start: usize,
+    col_end: usize,
+}
+
+/// How the color seen along a camera ray is computed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Integrator {
+    /// Direct lighting plus perfect mirror reflection and refraction
+    #[default]
+    Whitted,
+    /// Monte Carlo path tracing, with indirect lighting (see `PathTracer`)
+    Path,
+}
+
+impl FromStr for Integrator {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "whitted" => Ok(Integrator::Whitted),
+            "path" => Ok(Integrator::Path),
+            _ => Err(()),
+        }
+    }
+}
 
 pub struct RayTracer;
 
 impl RayTracer {
+    /// Render the scene, using all available cores when the `parallel`
+    /// feature is enabled
+    #[cfg(feature = "parallel")]
     pub fn render(&self, scene: &Scene) -> Image {
-        let mut img = Image::new(scene.resolution.0, scene.resolution.1)
-            .with_background(scene.background);
-
-        let viewport_height = 2.0 * scene.camera.vert_half_angle.tan();
-        let viewport_width = viewport_height
-            * (scene.resolution.0 as f64 / scene.resolution.1 as f64);
-        let pixel_width = viewport_width / scene.resolution.0 as f64;
-        let pixel_height = viewport_height / scene.resolution.1 as f64;
-
-        // The upper-left-most pixel
-        // 0.5s are to center the rays on each pixel
-        let upper_left = scene.camera.position
-            + scene.camera.direction
-            + scene.camera.up
-                * pixel_height
-                * (scene.resolution.1 as f64 / 2.0 - 0.5)
-            - scene.camera.right
-                * pixel_width
-                * (scene.resolution.0 as f64 / 2.0 - 0.5);
-
-        let mut done = 0;
-        for row in 0..scene.resolution.1 {
-            for col in 0..scene.resolution.0 {
-                // Compute the ray shooting from the eye
-                let image_plane_location = upper_left
-                    - scene.camera.up * pixel_height * row as f64
-                    + scene.camera.right * pixel_width * col as f64;
-                let ray_direction =
-                    image_plane_location - scene.camera.position;
-                let ray = Ray::new(scene.camera.position, ray_direction);
-                let color = self.trace_ray(scene, &ray, 0);
-                img.set_pixel(row, col, color);
-
-                done += 1;
-                if done % 10000 == 0 {
-                    info!(
-                        "Ray casting: {:.0}%",
-                        100.0 * (done as f64
-                            / (scene.resolution.0 * scene.resolution.1) as f64),
-                    );
-                }
+        let done = AtomicUsize::new(0);
+        let rendered: Vec<_> = tiles(scene.resolution)
+            .into_par_iter()
+            .map(|tile| (tile, self.render_tile(scene, tile, &done)))
+            .collect();
+        assemble(scene, rendered)
+    }
+
+    /// Render the scene
+    #[cfg(not(feature = "parallel"))]
+    pub fn render(&self, scene: &Scene) -> Image {
+        self.render_serial(scene)
+    }
+
+    /// Render the scene one tile at a time on the current thread
+    pub fn render_serial(&self, scene: &Scene) -> Image {
+        let done = AtomicUsize::new(0);
+        let rendered: Vec<_> = tiles(scene.resolution)
+            .into_iter()
+            .map(|tile| (tile, self.render_tile(scene, tile, &done)))
+            .collect();
+        assemble(scene, rendered)
+    }
+
+    /// Render the pixels of a tile, row by row
+    fn render_tile(
+        &self,
+        scene: &Scene,
+        tile: Tile,
+        done: &AtomicUsize,
+    ) -> Vec<Pixel> {
+        let mut pixels = Vec::with_capacity(
+            (tile.row_end - tile.row_start) * (tile.col_end - tile.col_start),
+        );
+        for row in tile.row_start..tile.row_end {
+            for col in tile.col_start..tile.col_end {
+                pixels.push(self.render_pixel(scene, row, col));
             }
         }
 
-        img
+        let before = done.fetch_add(pixels.len(), Ordering::Relaxed);
+        let after = before + pixels.len();
+        if after / PROGRESS_INTERVAL > before / PROGRESS_INTERVAL {
+            info!(
+                "Ray casting: {:.0}%",
+                100.0 * after as f64
+                    / (scene.resolution.0 * scene.resolution.1) as f64,
+            );
+        }
+        pixels
     }
 
-    fn trace_ray(&self, scene: &Scene, ray: &Ray, depth: usize) -> Pixel {
-        let mut closest_intersection =
-            Intersection::new(MAX_VECTOR3, MAX_VECTOR3);
+    /// Trace all the samples for one pixel and combine them with the
+    /// scene's reconstruction filter
+    pub fn render_pixel(&self, scene: &Scene, row: usize, col: usize) -> Pixel {
+        let mut rng = Rng::for_pixel(row, col);
+        let samples = scene.sampler.samples(&mut rng);
 
-        if depth > scene.max_depth {
+        let mut sum = Pixel::from_rgba_unclamped(0.0, 0.0, 0.0, 0.0);
+        let mut total_weight = 0.0;
+        for &(dx, dy, weight) in &samples {
+            let (row, col) = (row as f64 + dy, col as f64 + dx);
+            let ray = if scene.camera.aperture_radius > 0.0 {
+                let lens = (rng.next_f64(), rng.next_f64());
+                scene.camera.lens_ray(scene.resolution, row, col, lens)
+            } else {
+                scene.camera.primary_ray(scene.resolution, row, col)
+            };
+            // Projections with no ray here (e.g. outside a fisheye's
+            // circle) see nothing
+            let color = match (ray, scene.integrator) {
+                (None, _) => Pixel::from_rgba(0.0, 0.0, 0.0, 0.0),
+                (Some(ray), Integrator::Whitted) => {
+                    self.trace_ray(scene, &ray, 0, &[], &mut rng)
+                }
+                (Some(ray), Integrator::Path) => {
+                    PathTracer.radiance(scene, &ray, &mut rng)
+                }
+            };
+            sum.r += color.r * weight;
+            sum.g += color.g * weight;
+            sum.b += color.b * weight;
+            sum.a += color.a * weight;
+            total_weight += weight;
+        }
+
+        if total_weight <= 0.0 {
             return Pixel::from_rgb(0.0, 0.0, 0.0);
         }
+        sum.r /= total_weight;
+        sum.g /= total_weight;
+        sum.b /= total_weight;
+        sum.a /= total_weight;
+        sum
+    }
 
-        let mut color = scene.background;
+    /// Trace a ray through the scene. `media` are the materials of the
+    /// objects the ray is inside of, innermost last.
+    fn trace_ray<'a>(
+        &self,
+        scene: &'a Scene,
+        ray: &Ray,
+        depth: usize,
+        media: &[&'a Material],
+        rng: &mut Rng,
+    ) -> Pixel {
+        if depth > scene.max_depth {
+            return Pixel::from_rgb(0.0, 0.0, 0.0);
+        }
 
-        for object in &scene.objects {
-            if let Some(intersection) = object.intersects(&ray) {
-                let distance = (intersection.point - ray.start).length();
-                if distance < (closest_intersection.point - ray.start).length()
-                {
-                    color = self.calculate_illumination(
-                        scene,
-                        object,
-                        &intersection,
-                        ray,
-                        depth,
-                    );
-                    closest_intersection = intersection;
+        match scene.closest_intersection(ray) {
+            Some((object, intersection)) => {
+                let color = self.calculate_illumination(
+                    scene,
+                    object,
+                    &intersection,
+                    ray,
+                    depth,
+                    media,
+                    rng,
+                );
+                match media.last() {
+                    // Absorbed on the way through the medium
+                    Some(medium) => {
+                        let distance =
+                            (intersection.point - ray.start).length();
+                        color * medium.transmittance(distance)
+                    }
+                    None => color,
                 }
             }
+            None => scene.background_color(ray.direction),
         }
-        color
     }
 
-    fn calculate_illumination(
+    #[allow(clippy::too_many_arguments)]
+    fn calculate_illumination<'a>(
         &self,
-        scene: &Scene,
-        object: &Box<Object>,
+        scene: &'a Scene,
+        object: &'a Object,
         intersection: &Intersection,
         ray: &Ray,
         depth: usize,
+        media: &[&'a Material],
+        rng: &mut Rng,
     ) -> Pixel {
-        // Start with ambient light
+        let material = object.material().at(intersection);
+
+        // Start with ambient light, and any light the surface gives off
         let mut sum = Pixel::from_rgba_unclamped(0.0, 0.0, 0.0, 0.0);
 
-        sum = sum + object.material().ambient * scene.ambient_light;
+        sum = sum + material.ambient * scene.ambient_light;
+        sum = sum + material.emitted();
 
         for light in &scene.lights {
-            let to_light = light.direction_to_light(intersection);
-
-            // Calculate shadows
-            let in_shadow = scene.objects.iter().any(|object| {
-                object
-                    .intersects(&Ray::with_t_max(
-                        intersection.point,
-                        to_light.normalized(),
-                        light.distance_to_light(intersection),
-                    )).is_some()
-            });
+            // Each sample gets an equal share of the light
+            let points = sampler::stratified(light.sample_count(), rng);
+            let scale = 1.0 / points.len() as f64;
+            for &point in &points {
+                let sample = light.sample(intersection, point);
 
-            if in_shadow {
-                continue;
-            }
+                // Calculate shadows
+                let in_shadow = scene.any_intersection(&Ray::with_t_max(
+                    intersection.point,
+                    sample.direction,
+                    sample.distance,
+                ));
 
-            sum = sum + light.diffuse(&intersection, &object.material());
+                if in_shadow {
+                    continue;
+                }
 
-            sum = sum + light.specular(
-                &scene.camera,
-                intersection,
-                &object.material(),
-            );
+                sum = sum
+                    + light.diffuse(&intersection, &material, &sample) * scale;
+
+                sum = sum
+                    + light.specular(
+                        &scene.camera,
+                        intersection,
+                        &material,
+                        &sample,
+                    ) * scale;
+            }
         }
 
+        let (refracted, reflectance) = refraction(ray, intersection, &material);
+
         let reflected =
             ray.reflect(intersection.point, intersection.surface_normal);
         sum = sum
-            + object.material().specular
-                * self.trace_ray(scene, &reflected, depth + 1);
+            + (material.specular + material.transmissive * reflectance)
+                * self.trace_ray(scene, &reflected, depth + 1, media, rng);
 
-        let refracted = ray.refract(
-            intersection.point,
-            intersection.surface_normal,
-            object.material().ior,
-        );
-        sum = sum
-            + object.material().transmissive
-                * self.trace_ray(scene, &refracted, depth + 1);
+        if let Some(refracted) = refracted {
+            let mut refracted_media = media.to_vec();
+            cross_surface(
+                &mut refracted_media,
+                object.material(),
+                ray,
+                intersection,
+            );
+            sum = sum
+                + material.transmissive
+                    * (1.0 - reflectance)
+                    * self.trace_ray(
+                        scene,
+                        &refracted,
+                        depth + 1,
+                        &refracted_media,
+                        rng,
+                    );
+        }
 
         sum
     }
 }
+
+/// The ray refracted through a surface (`None` for total internal
+/// reflection), and the fraction of the transmitted light that is reflected
+/// instead
+pub fn refraction(
+    ray: &Ray,
+    intersection: &Intersection,
+    material: &Material,
+) -> (Option<Ray>, f64) {
+    // Going into or out of the object?
+    let cos_incident = -ray.direction.dot(&intersection.surface_normal);
+    let (eta_incident, eta_transmitted) = if cos_incident > 0.0 {
+        (1.0, material.ior)
+    } else {
+        (material.ior, 1.0)
+    };
+    let refracted = ray.refract(
+        intersection.point,
+        intersection.surface_normal,
+        material.ior,
+    );
+    let reflectance = match refracted {
+        // Total internal reflection
+        None => 1.0,
+        Some(_) => material
+            .fresnel
+            .reflectance(cos_incident, eta_incident, eta_transmitted)
+            .unwrap_or(0.0),
+    };
+    (refracted, reflectance)
+}
+
+/// Update the materials a ray is inside of (innermost last) as it is
+/// refracted into or out of an object with the given material
+pub fn cross_surface<'a>(
+    media: &mut Vec<&'a Material>,
+    material: &'a Material,
+    ray: &Ray,
+    intersection: &Intersection,
+) {
+    if ray.direction.dot(&intersection.surface_normal) < 0.0 {
+        media.push(material);
+    } else if let Some(index) =
+        media.iter().rposition(|medium| ptr::eq(*medium, material))
+    {
+        media.remove(index);
+    }
+}
+
+/// Split an image into tiles, left to right and top to bottom
+fn tiles(resolution: (usize, usize)) -> Vec<Tile> {
+    let (width, height) = resolution;
+    let mut tiles = Vec::new();
+    for row_start in (0..height).step_by(TILE_SIZE) {
+        for col_start in (0..width).step_by(TILE_SIZE) {
+            tiles.push(Tile {
+                row_start,
+                row_end: (row_start + TILE_SIZE).min(height),
+                col_start,
+                col_end: (col_start + TILE_SIZE).min(width),
+            });
+        }
+    }
+    tiles
+}
+
+/// Copy rendered tiles into an image
+fn assemble(scene: &Scene, rendered: Vec<(Tile, Vec<Pixel>)>) -> Image {
+    let mut img = Image::new(scene.resolution.0, scene.resolution.1)
+        .with_background(scene.background);
+    for (tile, pixels) in rendered {
+        let rows = tile.row_start..tile.row_end;
+        let positions = rows.flat_map(|row| {
+            (tile.col_start..tile.col_end).map(move |col| (row, col))
+        });
+        for ((row, col), color) in positions.zip(pixels) {
+            img.set_pixel(row, col, color);
+        }
+    }
+    img
+}
+
+#[cfg(all(test, feature = "parallel"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_matches_serial() {
+        let scene = Scene::from_text(String::from(
+            "camera -6 2 -4 .77 0 .64 0 1 0 35\n\
+             resolution 100 70\n\
+             samples_per_pixel 4 jittered\n\
+             material .75 .75 .75 .75 .75 .75 .3 .3 .3 32 .2 .2 .2 1.5\n\
+             sphere 0 -50 0 50\n\
+             material 0 .7 0 0 .7 0 0 0 0 16 .9 .9 .9 1.1\n\
+             sphere 0 1.25 0 1\n\
+             point_light 10 10 10 0 5 0\n",
+        ))
+        .unwrap();
+        let parallel = RayTracer.render(&scene);
+        let serial = RayTracer.render_serial(&scene);
+        assert_eq!(parallel.to_bytes(), serial.to_bytes());
+    }
+}
@@ -0,0 +1,55 @@
+//! External files a scene refers to (meshes, textures, ...)
+//!
+//! In the browser there is no file system, so files are handed over ahead
+//! of time and looked up by name. Natively, anything that wasn't handed
+//! over is read from disk relative to the scene file.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Default, Clone)]
+pub struct Resources {
+    /// Files provided up front, keyed by the name used in the scene
+    files: HashMap<String, Vec<u8>>,
+
+    /// Directory to read other files from, if reading from disk is allowed
+    base_directory: Option<PathBuf>,
+}
+
+impl Resources {
+    /// Resources that fall back to reading files relative to a directory
+    pub fn from_directory(directory: PathBuf) -> Self {
+        Self {
+            files: HashMap::new(),
+            base_directory: Some(directory),
+        }
+    }
+
+    /// Provide the contents of a file up front
+    pub fn add_file(&mut self, name: &str, contents: Vec<u8>) {
+        self.files.insert(name.to_string(), contents);
+    }
+
+    /// Get the contents of a file
+    pub fn load(&self, name: &str) -> Result<Vec<u8>, String> {
+        if let Some(contents) = self.files.get(name) {
+            return Ok(contents.clone());
+        }
+        match self.base_directory {
+            Some(ref directory) => {
+                let path = directory.join(name);
+                fs::read(&path).map_err(|err| {
+                    format!("Unable to read {}: {}", path.display(), err)
+                })
+            }
+            None => Err(format!("File {} was not provided", name)),
+        }
+    }
+
+    /// Get the contents of a text file
+    pub fn load_text(&self, name: &str) -> Result<String, String> {
+        String::from_utf8(self.load(name)?)
+            .map_err(|_| format!("File {} is not valid UTF-8 text", name))
+    }
+}
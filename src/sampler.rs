@@ -0,0 +1,247 @@
+//! Choosing where in a pixel to shoot rays, and how to combine the results
+//!
+//! With more than one sample per pixel, sample positions are spread over
+//! the support of the reconstruction filter and the traced colors are
+//! averaged, weighted by the filter.
+//!
+//! Also has helpers for turning random numbers into points and directions.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::random::Rng;
+use crate::vector::Vector3;
+
+/// How sample positions are laid out inside a pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplePattern {
+    /// Evenly spaced grid
+    Grid,
+    /// One random sample inside each cell of a grid (stratified)
+    Jittered,
+    /// Uniformly random samples
+    Random,
+}
+
+impl FromStr for SamplePattern {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grid" | "regular" => Ok(SamplePattern::Grid),
+            "jittered" | "stratified" => Ok(SamplePattern::Jittered),
+            "random" => Ok(SamplePattern::Random),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How samples are weighted based on their distance from the pixel center
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReconstructionFilter {
+    Box,
+    Tent,
+    Gaussian,
+}
+
+impl ReconstructionFilter {
+    /// The default radius (in pixels) for each filter
+    pub fn default_radius(self) -> f64 {
+        match self {
+            ReconstructionFilter::Box => 0.5,
+            ReconstructionFilter::Tent => 1.0,
+            ReconstructionFilter::Gaussian => 1.5,
+        }
+    }
+}
+
+impl FromStr for ReconstructionFilter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(ReconstructionFilter::Box),
+            "tent" | "triangle" => Ok(ReconstructionFilter::Tent),
+            "gaussian" => Ok(ReconstructionFilter::Gaussian),
+            _ => Err(()),
+        }
+    }
+}
+
+// Falloff of the Gaussian filter
+const GAUSSIAN_ALPHA: f64 = 2.0;
+
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub samples_per_pixel: usize,
+    pub pattern: SamplePattern,
+    pub filter: ReconstructionFilter,
+    /// Half-width of the filter, in pixels
+    pub filter_radius: f64,
+}
+
+impl Default for Sampler {
+    /// One ray through the center of each pixel
+    fn default() -> Self {
+        Self {
+            samples_per_pixel: 1,
+            pattern: SamplePattern::Grid,
+            filter: ReconstructionFilter::Box,
+            filter_radius: ReconstructionFilter::Box.default_radius(),
+        }
+    }
+}
+
+impl Sampler {
+    /// Sample offsets from the center of a pixel (in pixels), each with the
+    /// filter weight to use for it
+    pub fn samples(&self, rng: &mut Rng) -> Vec<(f64, f64, f64)> {
+        let count = self.samples_per_pixel.max(1);
+        let (rows, cols) = grid_dimensions(count);
+        let width = 2.0 * self.filter_radius;
+
+        (0..count)
+            .map(|i| {
+                let (u, v) = match self.pattern {
+                    SamplePattern::Grid => (
+                        ((i % cols) as f64 + 0.5) / cols as f64,
+                        ((i / cols) as f64 + 0.5) / rows as f64,
+                    ),
+                    SamplePattern::Jittered => (
+                        ((i % cols) as f64 + rng.next_f64()) / cols as f64,
+                        ((i / cols) as f64 + rng.next_f64()) / rows as f64,
+                    ),
+                    SamplePattern::Random => (rng.next_f64(), rng.next_f64()),
+                };
+                let dx = (u - 0.5) * width;
+                let dy = (v - 0.5) * width;
+                (dx, dy, self.weight(dx, dy))
+            })
+            .collect()
+    }
+
+    /// Filter weight for a sample at the given offset from the pixel center
+    pub fn weight(&self, dx: f64, dy: f64) -> f64 {
+        let radius = self.filter_radius;
+        match self.filter {
+            ReconstructionFilter::Box => 1.0,
+            ReconstructionFilter::Tent => {
+                (1.0 - dx.abs() / radius).max(0.0)
+                    * (1.0 - dy.abs() / radius).max(0.0)
+            }
+            ReconstructionFilter::Gaussian => {
+                let gaussian = |x: f64| {
+                    ((-GAUSSIAN_ALPHA * x * x).exp()
+                        - (-GAUSSIAN_ALPHA * radius * radius).exp())
+                    .max(0.0)
+                };
+                gaussian(dx) * gaussian(dy)
+            }
+        }
+    }
+}
+
+/// `count` points in the unit square, one in each cell of a grid
+pub fn stratified(count: usize, rng: &mut Rng) -> Vec<(f64, f64)> {
+    let (rows, cols) = grid_dimensions(count.max(1));
+    (0..rows * cols)
+        .map(|i| {
+            (
+                ((i % cols) as f64 + rng.next_f64()) / cols as f64,
+                ((i / cols) as f64 + rng.next_f64()) / rows as f64,
+            )
+        })
+        .collect()
+}
+
+/// Two unit vectors perpendicular to `normal` and to each other
+pub fn tangents(normal: Vector3) -> (Vector3, Vector3) {
+    let helper = if normal.x.abs() > 0.9 {
+        Vector3::new(0.0, 1.0, 0.0)
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = normal.cross(&helper).normalized();
+    (tangent, normal.cross(&tangent))
+}
+
+/// Map the unit square onto the unit disk, keeping strata evenly sized
+/// (Shirley and Chiu's concentric mapping)
+pub fn concentric_disk(u: f64, v: f64) -> (f64, f64) {
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (radius, angle) = if a.abs() > b.abs() {
+        (a, PI / 4.0 * (b / a))
+    } else {
+        (b, PI / 2.0 - PI / 4.0 * (a / b))
+    };
+    (radius * angle.cos(), radius * angle.sin())
+}
+
+/// A direction in the hemisphere around `normal`, more likely the closer it
+/// is to the normal (the density is cos(angle) / π)
+pub fn cosine_hemisphere(normal: Vector3, random: (f64, f64)) -> Vector3 {
+    // Project points spread evenly over the unit disk up onto the hemisphere
+    let (x, y) = concentric_disk(random.0, random.1);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    let (tangent, bitangent) = tangents(normal);
+    (tangent * x + bitangent * y + normal * z).normalized()
+}
+
+/// Split `count` samples into a grid that is as close to square as possible
+fn grid_dimensions(count: usize) -> (usize, usize) {
+    let mut rows = (count as f64).sqrt() as usize;
+    while count % rows != 0 {
+        rows -= 1;
+    }
+    (rows, count / rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_pixel_center() {
+        let samples = Sampler::default().samples(&mut Rng::new(0));
+        assert_eq!(samples, vec![(0.0, 0.0, 1.0)]);
+    }
+
+    #[test]
+    fn jittered_stays_in_strata() {
+        let sampler = Sampler {
+            samples_per_pixel: 6,
+            pattern: SamplePattern::Jittered,
+            ..Sampler::default()
+        };
+        let samples = sampler.samples(&mut Rng::new(7));
+        assert_eq!(samples.len(), 6);
+        // 2 rows by 3 columns of cells, each 1/3 by 1/2 of a pixel
+        for (i, &(dx, dy, _)) in samples.iter().enumerate() {
+            let (cell_col, cell_row) = ((i % 3) as f64, (i / 3) as f64);
+            assert!(
+                dx + 0.5 >= cell_col / 3.0 && dx + 0.5 < (cell_col + 1.0) / 3.0
+            );
+            assert!(
+                dy + 0.5 >= cell_row / 2.0 && dy + 0.5 < (cell_row + 1.0) / 2.0
+            );
+        }
+    }
+
+    #[test]
+    fn cosine_hemisphere_favors_the_normal() {
+        let normal = Vector3::new(0.0, 0.6, 0.8);
+        let points = stratified(400, &mut Rng::new(3));
+        let mut mean_cos = 0.0;
+        for &point in &points {
+            let direction = cosine_hemisphere(normal, point);
+            assert!((direction.length() - 1.0).abs() < 1e-9);
+            assert!(direction.dot(&normal) >= 0.0);
+            mean_cos += direction.dot(&normal) / points.len() as f64;
+        }
+        // The mean of cos(angle) with a cos(angle) / π density is 2/3
+        assert!((mean_cos - 2.0 / 3.0).abs() < 0.01);
+    }
+}
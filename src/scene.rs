@@ -1,20 +1,56 @@
 //! A simple description of a scene used for ray tracing
 
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::str::FromStr;
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
 
-use crate::camera::Camera;
+use crate::bounding_box::BoundingBox;
+use crate::bvh::Bvh;
+use crate::camera::{Aperture, Camera, FovAxis};
+use crate::directive::Directive;
+use crate::environment::{Environment, EnvironmentLayout};
+use crate::fresnel::Fresnel;
+use crate::image::Image;
+use crate::intersection::Intersection;
+use crate::lights::area_light::{AreaLight, LightShape};
 use crate::lights::directional_light::DirectionalLight;
+use crate::lights::environment_light::EnvironmentLight;
 use crate::lights::light::Light;
 use crate::lights::point_light::PointLight;
 use crate::lights::spot_light::SpotLight;
-use crate::material::Material;
+use crate::material::{ColorChannel, Material};
+use crate::matrix::Matrix4;
+use crate::obj::ObjMesh;
+use crate::objects::cone::Cone;
+use crate::objects::csg::{Csg, CsgOperation};
+use crate::objects::cuboid::Cuboid;
+use crate::objects::disk::Disk;
+use crate::objects::instance::Instance;
 use crate::objects::object::Object;
 use crate::objects::plane::Plane;
+use crate::objects::quadric::Quadric;
+use crate::objects::sdf::{Sdf, SdfOperation, SdfShape};
 use crate::objects::sphere::Sphere;
-use crate::objects::triangle::Triangle;
+use crate::objects::torus::Torus;
+use crate::objects::triangle;
+use crate::objects::triangle_mesh::{TriangleMesh, TriangleMeshBuilder};
 use crate::pixel::Pixel;
+use crate::projections::fisheye::Fisheye;
+use crate::projections::orthographic::Orthographic;
+use crate::projections::panorama::Panorama;
+use crate::projections::perspective::Perspective;
+use crate::projections::projection::Projection;
+use crate::ray::Ray;
+use crate::ray_tracer::Integrator;
+use crate::resources::Resources;
+use crate::sampler::{ReconstructionFilter, SamplePattern, Sampler};
+use crate::scene_error::SceneError;
+use crate::textures::image_texture::{ImageTexture, TextureFilter, WrapMode};
+use crate::textures::procedural::{Pattern, ProceduralTexture};
+use crate::textures::texture::{Texture, TextureSpace};
 use crate::vector::Vector3;
 
 #[derive(Debug)]
@@ -22,7 +58,7 @@ pub struct Scene {
     /// The scene's camera
     pub camera: Camera,
 
-    /// The resolution of the output image
+    /// The resolution of the output image. Change it with `set_resolution`.
     pub resolution: (usize, usize),
 
     /// The file path for the output image
@@ -31,17 +67,42 @@ pub struct Scene {
     /// The background color
     pub background: Pixel,
 
+    /// Image seen in place of the background, if any
+    pub environment: Option<Arc<Environment>>,
+
+    /// Whether an `EnvironmentLight` for the environment is one of the
+    /// lights
+    pub environment_light: bool,
+
     /// All the objects in the scene
     pub objects: Vec<Box<Object>>,
 
+    /// Bounding volume hierarchy over the bounded objects, built by
+    /// `build_bvh` once all objects have been added
+    pub bvh: Bvh,
+
+    /// Indices of objects with no bounding box (e.g. planes), which are
+    /// tested one by one
+    pub unbounded_objects: Vec<usize>,
+
     /// Ambient lighting in a scene
     pub ambient_light: Pixel,
 
     /// All other lights in the scene
     pub lights: Vec<Box<Light>>,
 
+    /// For each object, whether it glows and stands in as one of the
+    /// `lights`, so sampling those already finds its light
+    pub light_objects: Vec<bool>,
+
     /// The max depth of a ray
     pub max_depth: usize,
+
+    /// How many rays to shoot per pixel, and how to combine them
+    pub sampler: Sampler,
+
+    /// How the color seen along each ray is computed
+    pub integrator: Integrator,
 }
 
 impl Default for Scene {
@@ -51,45 +112,66 @@ impl Default for Scene {
             resolution: (640, 480),
             output_image: String::from("./raytraced.bmp"),
             background: Pixel::from_rgb(0.0, 0.0, 0.0),
+            environment: None,
+            environment_light: false,
             objects: Vec::new(),
+            bvh: Bvh::default(),
+            unbounded_objects: Vec::new(),
             ambient_light: Pixel::from_rgb(0.0, 0.0, 0.0),
             lights: Vec::new(),
+            light_objects: Vec::new(),
             max_depth: 5,
+            sampler: Sampler::default(),
+            integrator: Integrator::default(),
         }
     }
 }
 
 impl Scene {
-    pub fn from_file(scene_file: &str) -> Self {
-        let mut scene_file =
-            File::open(scene_file).expect("Unable to open scene file");
+    pub fn from_file(scene_file: &str) -> Result<Self, SceneError> {
         let mut scene_contents = String::new();
-        scene_file
-            .read_to_string(&mut scene_contents)
-            .expect("Unable to read scene file");
+        File::open(scene_file)
+            .and_then(|mut file| file.read_to_string(&mut scene_contents))
+            .map_err(|err| {
+                SceneError::new(
+                    0,
+                    "",
+                    format!(
+                        "Unable to read scene file {}: {}",
+                        scene_file, err
+                    ),
+                )
+            })?;
 
-        Self::from_text(scene_contents)
+        // Files the scene refers to are relative to the scene file
+        let directory = Path::new(scene_file)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Self::from_text_with_resources(
+            scene_contents,
+            &Resources::from_directory(directory),
+        )
     }
 
-    pub fn from_text(scene_contents: String) -> Self {
-        let scene_lines: Vec<_> = scene_contents
-            .lines()
-            .filter(|&line| !line.starts_with('#') && !line.is_empty())
-            .map(|line| {
-                if let Some(index) = line.find('#') {
-                    &line[..index]
-                } else {
-                    line
-                }
-            }).collect();
-        let tokens_per_line: Vec<Vec<_>> = scene_lines
-            .iter()
-            .map(|&line| line.split_whitespace().collect())
-            .collect();
+    pub fn from_text(scene_contents: String) -> Result<Self, SceneError> {
+        Self::from_text_with_resources(scene_contents, &Resources::default())
+    }
 
+    /// Load a scene, looking up any files it refers to (e.g. OBJ meshes) in
+    /// `resources`
+    pub fn from_text_with_resources(
+        scene_contents: String,
+        resources: &Resources,
+    ) -> Result<Self, SceneError> {
         let mut scene = Self::default();
         let mut current_material = Material::default();
 
+        // `triangle` and `normal_triangle` faces with the same material are
+        // collected into one mesh
+        let mut pending_mesh = TriangleMeshBuilder::default();
+        let mut placement = Placement::default();
+
         let mut vertices = Vec::new();
         let mut vertices_so_far = 0;
         let mut max_vertices = None;
@@ -98,44 +180,421 @@ impl Scene {
         let mut normals_so_far = 0;
         let mut max_normals = None;
 
-        for line in &tokens_per_line {
-            if line.is_empty() {
-                continue;
-            }
-            match line[0] {
+        let mut texcoords = Vec::new();
+
+        // Open `sdf` groups and their shapes so far, innermost last
+        let mut sdf_groups: Vec<(SdfOperation, Vec<SdfShape>)> = Vec::new();
+
+        // Decoded images, by file name
+        let mut images: HashMap<String, Arc<Image>> = HashMap::new();
+
+        for (line_index, text) in scene_contents.lines().enumerate() {
+            let line = match Directive::parse(line_index + 1, text) {
+                Some(line) => line,
+                None => continue,
+            };
+            match line.name {
                 "camera" => {
-                    assert_eq!(line.len(), 11);
-                    let float_tokens = parse_full_slice(&line[1..]);
-                    scene.camera = Camera::from_parameters(&float_tokens)
+                    line.expect_arguments(10)?;
+                    let float_tokens = line.floats(0..10)?;
+                    let camera = Camera::from_parameters(&float_tokens);
+                    if !camera.right.length().is_finite() {
+                        warn!(
+                            "Camera on line {} has a zero direction or up \
+                             vector, or they are parallel, so it can't see \
+                             anything",
+                            line.line
+                        );
+                    }
+                    // Keep any lens and projection set up by
+                    // `depth_of_field` and `projection`
+                    let previous = mem::take(&mut scene.camera);
+                    scene.camera = camera.with_optics_of(previous);
+                }
+                "look_at" => {
+                    line.expect_one_of(&[10, 11])?;
+                    let float_tokens = line.floats(0..10)?;
+                    let fov = float_tokens[9];
+                    if !(fov > 0.0 && fov < 180.0) {
+                        return Err(line.argument_error(
+                            9,
+                            String::from(
+                                "field of view must be between 0 and 180 \
+                                 degrees",
+                            ),
+                        ));
+                    }
+                    let axis = if line.len() == 11 {
+                        line.parse_argument::<FovAxis>(
+                            10,
+                            "a field of view axis (horizontal or vertical)",
+                        )?
+                    } else {
+                        FovAxis::Vertical
+                    };
+                    let mut camera = Camera::look_at(
+                        Vector3::from(&float_tokens[0..3]),
+                        Vector3::from(&float_tokens[3..6]),
+                        Vector3::from(&float_tokens[6..9]),
+                        Camera::vert_half_angle(
+                            fov.to_radians(),
+                            axis,
+                            scene.aspect(),
+                        ),
+                    )
+                    .map_err(|message| line.error(message))?;
+                    // A horizontal field of view depends on the resolution,
+                    // which may change later
+                    if axis == FovAxis::Horizontal {
+                        camera.horizontal_fov = Some(fov.to_radians());
+                    }
+                    let previous = mem::take(&mut scene.camera);
+                    scene.camera = camera.with_optics_of(previous);
+                }
+                "projection" => {
+                    let projection: Box<Projection> = match line.argument(0)? {
+                        "perspective" => {
+                            line.expect_arguments(1)?;
+                            Box::new(Perspective)
+                        }
+                        "orthographic" => {
+                            line.expect_arguments(2)?;
+                            let height = line.floats(1..2)?[0];
+                            Box::new(Orthographic {
+                                height: positive(&line, 1, height)?,
+                            })
+                        }
+                        "fisheye" => {
+                            line.expect_arguments(2)?;
+                            let field_of_view = line.floats(1..2)?[0];
+                            if !(field_of_view > 0.0 && field_of_view <= 360.0)
+                            {
+                                return Err(line.argument_error(
+                                    1,
+                                    String::from(
+                                        "field of view must be between 0 and \
+                                         360 degrees",
+                                    ),
+                                ));
+                            }
+                            Box::new(Fisheye {
+                                field_of_view: field_of_view.to_radians(),
+                            })
+                        }
+                        "panorama" | "equirect" | "equirectangular" => {
+                            line.expect_arguments(1)?;
+                            Box::new(Panorama)
+                        }
+                        _ => {
+                            return Err(line.argument_error(
+                                0,
+                                String::from(
+                                    "expected a projection (perspective, \
+                                     orthographic, fisheye or panorama)",
+                                ),
+                            ))
+                        }
+                    };
+                    scene.camera.projection = projection;
+                }
+                "depth_of_field" => {
+                    line.expect_one_of(&[2, 3, 4, 5])?;
+                    let float_tokens = line.floats(0..2)?;
+                    if float_tokens[0] < 0.0 {
+                        return Err(line.argument_error(
+                            0,
+                            String::from("aperture radius can't be negative"),
+                        ));
+                    }
+                    let focal_distance = positive(&line, 1, float_tokens[1])?;
+                    let shape = if line.len() > 2 {
+                        line.argument(2)?
+                    } else {
+                        "circle"
+                    };
+                    let aperture =
+                        match shape {
+                            "circle" => {
+                                line.expect_one_of(&[2, 3])?;
+                                Aperture::Circle
+                            }
+                            "polygon" => {
+                                line.expect_one_of(&[4, 5])?;
+                                let sides = line.parse_argument::<usize>(
+                                    3,
+                                    "a number of sides",
+                                )?;
+                                if sides < 3 {
+                                    return Err(line.argument_error(
+                                        3,
+                                        String::from("needs at least 3 sides"),
+                                    ));
+                                }
+                                let rotation = if line.len() == 5 {
+                                    line.parse_argument::<f64>(
+                                        4,
+                                        "an angle in degrees",
+                                    )?
+                                } else {
+                                    0.0
+                                };
+                                Aperture::Polygon {
+                                    sides,
+                                    rotation: rotation.to_radians(),
+                                }
+                            }
+                            _ => return Err(line.argument_error(
+                                2,
+                                String::from(
+                                    "expected an aperture shape (circle or \
+                                     polygon)",
+                                ),
+                            )),
+                        };
+                    scene.camera = Camera {
+                        aperture_radius: float_tokens[0],
+                        focal_distance,
+                        aperture,
+                        ..scene.camera
+                    };
                 }
                 "output_image" => {
-                    assert_eq!(line.len(), 2);
-                    scene.output_image = line[1].to_string();
+                    line.expect_arguments(1)?;
+                    scene.output_image = line.argument(0)?.to_string();
                 }
                 "background" => {
-                    assert_eq!(line.len(), 4);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(3)?;
+                    let float_tokens = line.floats(0..3)?;
                     scene.background = Pixel::from(float_tokens.as_slice());
                 }
+                "environment" => {
+                    line.expect_one_of(&[1, 2, 3, 4])?;
+                    let image = load_image(&line, 0, resources, &mut images)?;
+                    let layout = if line.len() > 1 {
+                        line.parse_argument::<EnvironmentLayout>(
+                            1,
+                            "an environment layout (equirect or cube)",
+                        )?
+                    } else {
+                        EnvironmentLayout::Equirectangular
+                    };
+                    let rotation = if line.len() > 2 {
+                        line.parse_argument::<f64>(2, "an angle in degrees")?
+                    } else {
+                        0.0
+                    };
+                    let intensity = if line.len() > 3 {
+                        let intensity =
+                            line.parse_argument::<f64>(3, "a number")?;
+                        positive(&line, 3, intensity)?
+                    } else {
+                        1.0
+                    };
+                    let environment = Environment::new(
+                        image,
+                        layout,
+                        rotation.to_radians(),
+                        intensity,
+                    )
+                    .map_err(|message| line.argument_error(0, message))?;
+                    scene.environment = Some(Arc::new(environment));
+                }
+                "environment_light" => {
+                    line.expect_one_of(&[0, 1])?;
+                    let samples = light_samples(&line, 0)?;
+                    let environment = match scene.environment {
+                        Some(ref environment) => environment.clone(),
+                        None => {
+                            return Err(line.error(String::from(
+                                "needs an `environment` first",
+                            )))
+                        }
+                    };
+                    scene.lights.push(Box::new(EnvironmentLight::new(
+                        environment,
+                        samples,
+                    )));
+                    scene.environment_light = true;
+                }
                 "film_resolution" | "resolution" => {
-                    assert_eq!(line.len(), 3);
-                    let width_height = parse_full_slice(&line[1..]);
-                    scene.resolution = (width_height[0], width_height[1]);
+                    line.expect_arguments(2)?;
+                    let width_height = line.integers(0..2)?;
+                    if width_height.contains(&0) {
+                        return Err(line.error(String::from(
+                            "resolution must be at least 1x1",
+                        )));
+                    }
+                    scene.set_resolution((width_height[0], width_height[1]));
                 }
                 "sphere" => {
-                    assert_eq!(line.len(), 5);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(4)?;
+                    let float_tokens = line.floats(0..4)?;
                     let position = Vector3::from(&float_tokens[..3]);
                     let radius = float_tokens[3];
-                    scene.objects.push(Box::new(Sphere::new(
+                    placement.add(
+                        &mut scene,
+                        Box::new(Sphere::new(
+                            radius,
+                            position,
+                            current_material.clone(),
+                        )),
+                    );
+                }
+                "box" => {
+                    line.expect_arguments(6)?;
+                    let float_tokens = line.floats(0..6)?;
+                    let (corner1, corner2) = (
+                        Vector3::from(&float_tokens[..3]),
+                        Vector3::from(&float_tokens[3..]),
+                    );
+                    if let Some(axis) = (0..3)
+                        .find(|&axis| corner1.axis(axis) == corner2.axis(axis))
+                    {
+                        return Err(line.argument_error(
+                            axis + 3,
+                            String::from(
+                                "box must have some size along each axis",
+                            ),
+                        ));
+                    }
+                    placement.add(
+                        &mut scene,
+                        Box::new(Cuboid::new(
+                            corner1,
+                            corner2,
+                            current_material.clone(),
+                        )),
+                    );
+                }
+                "cylinder" | "cone" => {
+                    let radii = if line.name == "cone" { 2 } else { 1 };
+                    line.expect_one_of(&[6 + radii, 7 + radii])?;
+                    let float_tokens = line.floats(0..6 + radii)?;
+                    let (base, top) = (
+                        Vector3::from(&float_tokens[..3]),
+                        Vector3::from(&float_tokens[3..6]),
+                    );
+                    if base == top {
+                        return Err(line.argument_error(
+                            3,
+                            String::from("ends must be different points"),
+                        ));
+                    }
+                    let radii = &float_tokens[6..];
+                    if let Some(index) =
+                        radii.iter().position(|&radius| radius < 0.0)
+                    {
+                        return Err(line.argument_error(
+                            6 + index,
+                            String::from("radius can't be negative"),
+                        ));
+                    }
+                    if radii.iter().all(|&radius| radius == 0.0) {
+                        return Err(line.argument_error(
+                            6,
+                            String::from("radius must be positive"),
+                        ));
+                    }
+                    let capped = if line.len() > float_tokens.len() {
+                        parse_caps(&line, float_tokens.len())?
+                    } else {
+                        false
+                    };
+                    let cone = Cone::new(
+                        base,
+                        top,
+                        radii[0],
+                        radii[radii.len() - 1],
+                        current_material.clone(),
+                    );
+                    placement.add(&mut scene, Box::new(cone.with_caps(capped)));
+                }
+                "disk" => {
+                    line.expect_one_of(&[7, 8])?;
+                    let float_tokens = line.floats(0..line.len())?;
+                    let normal = Vector3::from(&float_tokens[3..6]);
+                    if normal.length() == 0.0 {
+                        return Err(line.argument_error(
+                            3,
+                            String::from("normal can't be zero"),
+                        ));
+                    }
+                    let radius = positive(&line, 6, float_tokens[6])?;
+                    let inner_radius =
+                        float_tokens.get(7).cloned().unwrap_or(0.0);
+                    if inner_radius < 0.0 || inner_radius >= radius {
+                        return Err(line.argument_error(
+                            7,
+                            String::from(
+                                "inner radius must be between 0 and the radius",
+                            ),
+                        ));
+                    }
+                    let disk = Disk::new(
+                        Vector3::from(&float_tokens[..3]),
+                        normal,
                         radius,
-                        position,
                         current_material.clone(),
-                    )));
+                    );
+                    placement.add(
+                        &mut scene,
+                        Box::new(disk.with_inner_radius(inner_radius)),
+                    );
+                }
+                "torus" => {
+                    line.expect_arguments(8)?;
+                    let float_tokens = line.floats(0..8)?;
+                    let axis = Vector3::from(&float_tokens[3..6]);
+                    if axis.length() == 0.0 {
+                        return Err(line.argument_error(
+                            3,
+                            String::from("axis can't be zero"),
+                        ));
+                    }
+                    let major_radius = positive(&line, 6, float_tokens[6])?;
+                    let minor_radius = positive(&line, 7, float_tokens[7])?;
+                    placement.add(
+                        &mut scene,
+                        Box::new(Torus::new(
+                            Vector3::from(&float_tokens[..3]),
+                            axis,
+                            major_radius,
+                            minor_radius,
+                            current_material.clone(),
+                        )),
+                    );
+                }
+                "quadric" => {
+                    line.expect_one_of(&[10, 16])?;
+                    let float_tokens = line.floats(0..line.len())?;
+                    let mut coefficients = [0.0; 10];
+                    coefficients.copy_from_slice(&float_tokens[..10]);
+                    if coefficients[..9].iter().all(|&c| c == 0.0) {
+                        return Err(line.argument_error(
+                            0,
+                            String::from("quadric must have a variable term"),
+                        ));
+                    }
+                    let mut quadric =
+                        Quadric::new(coefficients, current_material.clone());
+                    if float_tokens.len() == 16 {
+                        let clip = BoundingBox::from_points(&[
+                            Vector3::from(&float_tokens[10..13]),
+                            Vector3::from(&float_tokens[13..]),
+                        ]);
+                        quadric = quadric.with_clip(clip);
+                    }
+                    placement.add(&mut scene, Box::new(quadric));
                 }
                 "material" => {
-                    assert_eq!(line.len(), 15);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(14)?;
+                    let float_tokens = line.floats(0..14)?;
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
                     let ambient = Pixel::from(&float_tokens[..3]);
                     let diffuse = Pixel::from(&float_tokens[3..6]);
                     let specular = Pixel::from(&float_tokens[6..9]);
@@ -150,13 +609,13 @@ impl Scene {
                     );
                 }
                 "ambient_light" => {
-                    assert_eq!(line.len(), 4);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(3)?;
+                    let float_tokens = line.floats(0..3)?;
                     scene.ambient_light = Pixel::from(float_tokens.as_slice());
                 }
                 "point_light" => {
-                    assert_eq!(line.len(), 7);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(6)?;
+                    let float_tokens = line.floats(0..6)?;
                     let color = Pixel::from_slice_unclamped(&float_tokens[..3]);
                     let position = Vector3::from(&float_tokens[3..]);
                     scene
@@ -164,8 +623,8 @@ impl Scene {
                         .push(Box::new(PointLight::new(color, position)));
                 }
                 "directional_light" => {
-                    assert_eq!(line.len(), 7);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(6)?;
+                    let float_tokens = line.floats(0..6)?;
                     let color = Pixel::from_slice_unclamped(&float_tokens[..3]);
                     let direction = Vector3::from(&float_tokens[3..]);
                     scene.lights.push(Box::new(DirectionalLight::new(
@@ -173,8 +632,8 @@ impl Scene {
                     )));
                 }
                 "spot_light" => {
-                    assert_eq!(line.len(), 12);
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(11)?;
+                    let float_tokens = line.floats(0..11)?;
                     let color = Pixel::from_slice_unclamped(&float_tokens[..3]);
                     let position = Vector3::from(&float_tokens[3..6]);
                     let direction = Vector3::from(&float_tokens[6..9]);
@@ -184,105 +643,1175 @@ impl Scene {
                         color, position, direction, angle1, angle2,
                     )));
                 }
+                "rect_light" => {
+                    // color, corner, two edges, optional sample count
+                    line.expect_one_of(&[12, 13])?;
+                    let float_tokens = line.floats(0..12)?;
+                    let edge1 = Vector3::from(&float_tokens[6..9]);
+                    let edge2 = Vector3::from(&float_tokens[9..12]);
+                    if edge1.cross(&edge2).length() == 0.0 {
+                        return Err(line.error(String::from(
+                            "rectangle edges must not be parallel",
+                        )));
+                    }
+                    let shape = LightShape::Rectangle {
+                        corner: Vector3::from(&float_tokens[3..6]),
+                        edge1,
+                        edge2,
+                    };
+                    scene.lights.push(Box::new(AreaLight::new(
+                        Pixel::from_slice_unclamped(&float_tokens[..3]),
+                        shape,
+                        light_samples(&line, 12)?,
+                    )));
+                }
+                "disk_light" => {
+                    // color, center, normal, radius, optional sample count
+                    line.expect_one_of(&[10, 11])?;
+                    let float_tokens = line.floats(0..10)?;
+                    let normal = Vector3::from(&float_tokens[6..9]);
+                    if normal.length() == 0.0 {
+                        return Err(line.argument_error(
+                            6,
+                            String::from("normal must not be zero"),
+                        ));
+                    }
+                    let shape = LightShape::Disk {
+                        center: Vector3::from(&float_tokens[3..6]),
+                        normal,
+                        radius: positive(&line, 9, float_tokens[9])?,
+                    };
+                    scene.lights.push(Box::new(AreaLight::new(
+                        Pixel::from_slice_unclamped(&float_tokens[..3]),
+                        shape,
+                        light_samples(&line, 10)?,
+                    )));
+                }
+                "sphere_light" => {
+                    // color, center, radius, optional sample count
+                    line.expect_one_of(&[7, 8])?;
+                    let float_tokens = line.floats(0..7)?;
+                    let shape = LightShape::Sphere {
+                        center: Vector3::from(&float_tokens[3..6]),
+                        radius: positive(&line, 6, float_tokens[6])?,
+                    };
+                    scene.lights.push(Box::new(AreaLight::new(
+                        Pixel::from_slice_unclamped(&float_tokens[..3]),
+                        shape,
+                        light_samples(&line, 7)?,
+                    )));
+                }
                 "max_depth" => {
-                    assert_eq!(line.len(), 2);
-                    scene.max_depth = line[1].parse::<usize>().unwrap_or(5);
+                    line.expect_arguments(1)?;
+                    scene.max_depth =
+                        line.parse_argument(0, "a non-negative integer")?;
+                }
+                "integrator" => {
+                    line.expect_arguments(1)?;
+                    scene.integrator = line.parse_argument::<Integrator>(
+                        0,
+                        "an integrator (whitted or path)",
+                    )?;
+                }
+                "samples_per_pixel" => {
+                    line.expect_one_of(&[1, 2])?;
+                    let samples = line.integers(0..1)?[0];
+                    if samples == 0 {
+                        return Err(line.argument_error(
+                            0,
+                            String::from("must take at least one sample"),
+                        ));
+                    }
+                    scene.sampler.samples_per_pixel = samples;
+                    if line.len() == 2 {
+                        scene.sampler.pattern = line
+                            .parse_argument::<SamplePattern>(
+                                1,
+                                "a sample pattern (grid, jittered or random)",
+                            )?;
+                    }
+                }
+                "pixel_filter" => {
+                    line.expect_one_of(&[1, 2])?;
+                    let filter = line.parse_argument::<ReconstructionFilter>(
+                        0,
+                        "a filter (box, tent or gaussian)",
+                    )?;
+                    scene.sampler.filter = filter;
+                    scene.sampler.filter_radius = if line.len() == 2 {
+                        positive(&line, 1, line.floats(1..2)?[0])?
+                    } else {
+                        filter.default_radius()
+                    };
                 }
                 "max_vertices" => {
-                    assert_eq!(line.len(), 2);
-                    max_vertices = Some(
-                        line[1]
-                            .parse::<usize>()
-                            .expect("Max vertices must be an integer"),
-                    );
-                    vertices.resize(max_vertices.unwrap(), Vector3::default());
+                    line.expect_arguments(1)?;
+                    let max = line.integers(0..1)?[0];
+                    max_vertices = Some(max);
+                    vertices.resize(max, Vector3::default());
                 }
                 "max_normals" => {
-                    assert_eq!(line.len(), 2);
-                    max_normals = Some(
-                        line[1]
-                            .parse::<usize>()
-                            .expect("Max normals must be an integer"),
-                    );
-                    normals.resize(max_normals.unwrap(), Vector3::default());
+                    line.expect_arguments(1)?;
+                    let max = line.integers(0..1)?[0];
+                    max_normals = Some(max);
+                    normals.resize(max, Vector3::default());
                 }
                 "vertex" => {
-                    assert_eq!(line.len(), 4);
-                    max_vertices.expect("Max vertices must be provided before specifying any vertices");
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(3)?;
+                    let max = max_vertices.ok_or_else(|| {
+                        line.error(String::from(
+                            "max_vertices must be provided before \
+                             specifying any vertices",
+                        ))
+                    })?;
+                    if vertices_so_far >= max {
+                        return Err(line.error(format!(
+                            "more than max_vertices ({}) vertices",
+                            max
+                        )));
+                    }
+                    let float_tokens = line.floats(0..3)?;
                     vertices[vertices_so_far] =
                         Vector3::from(float_tokens.as_slice());
                     vertices_so_far += 1;
                 }
                 "normal" => {
-                    assert_eq!(line.len(), 4);
-                    max_normals.expect("Max normals must be provided before specifying any normals");
-                    let float_tokens = parse_full_slice(&line[1..]);
+                    line.expect_arguments(3)?;
+                    let max = max_normals.ok_or_else(|| {
+                        line.error(String::from(
+                            "max_normals must be provided before \
+                             specifying any normals",
+                        ))
+                    })?;
+                    if normals_so_far >= max {
+                        return Err(line.error(format!(
+                            "more than max_normals ({}) normals",
+                            max
+                        )));
+                    }
+                    let float_tokens = line.floats(0..3)?;
                     normals[normals_so_far] =
                         Vector3::from(float_tokens.as_slice()).normalized();
                     normals_so_far += 1;
                 }
                 "triangle" => {
-                    assert_eq!(line.len(), 4);
-                    let indices: Vec<usize> = parse_full_slice(&line[1..]);
-                    for t in &indices {
-                        assert!(t < &vertices.len());
-                    }
-                    let (v1, v2, v3) = (
+                    line.expect_one_of(&[3, 6])?;
+                    let vertex_count = vertices_so_far.min(vertices.len());
+                    let indices = [
+                        line.index(0, vertex_count, "vertex")?,
+                        line.index(1, vertex_count, "vertex")?,
+                        line.index(2, vertex_count, "vertex")?,
+                    ];
+                    let normal = triangle::guess_normal(
                         vertices[indices[0]],
                         vertices[indices[1]],
                         vertices[indices[2]],
-                    );
-                    scene.objects.push(Box::new(Triangle::guess_normal(
-                        current_material.clone(),
-                        v1,
-                        v2,
-                        v3,
                         &scene.camera,
-                    )));
+                    );
+                    pending_mesh.add_flat_face(&vertices, indices, normal);
+                    if line.len() == 6 {
+                        pending_mesh.add_texcoords(
+                            &texcoords,
+                            texcoord_indices(&line, 3, texcoords.len())?,
+                        );
+                    }
                 }
                 "plane" => {
-                    assert_eq!(line.len(), 7);
-                    let float_tokens = parse_full_slice(&line[1..]);
-                    scene.objects.push(Box::new(Plane::new(
+                    line.expect_one_of(&[6, 7, 10])?;
+                    let float_tokens = line.floats(0..line.len())?;
+                    let mut plane = Plane::new(
                         current_material.clone(),
                         Vector3::from(&float_tokens[..3]),
-                        Vector3::from(&float_tokens[3..]),
-                    )));
+                        Vector3::from(&float_tokens[3..6]),
+                    );
+                    // Optional texture axis and scale
+                    if line.len() > 6 {
+                        let u_axis = match line.len() {
+                            10 => Vector3::from(&float_tokens[6..9]),
+                            _ => plane.u_axis,
+                        };
+                        let scale = float_tokens[line.len() - 1];
+                        if scale == 0.0 {
+                            return Err(line.argument_error(
+                                line.len() - 1,
+                                String::from("texture scale must not be 0"),
+                            ));
+                        }
+                        if u_axis.cross(&plane.normal).length() < 1e-9 {
+                            return Err(line.argument_error(
+                                6,
+                                String::from(
+                                    "texture axis must not be parallel to \
+                                     the normal",
+                                ),
+                            ));
+                        }
+                        plane = plane.with_uv_axis(u_axis, scale);
+                    }
+                    placement.add(&mut scene, Box::new(plane));
                 }
                 "normal_triangle" => {
-                    assert_eq!(line.len(), 7);
-                    let vert_indices: Vec<usize> =
-                        parse_full_slice(&line[1..4]);
-                    for t in &vert_indices {
-                        assert!(t < &vertices.len());
+                    line.expect_one_of(&[6, 9])?;
+                    let vertex_count = vertices_so_far.min(vertices.len());
+                    let normal_count = normals_so_far.min(normals.len());
+                    pending_mesh.add_face(
+                        &vertices,
+                        &normals,
+                        [
+                            line.index(0, vertex_count, "vertex")?,
+                            line.index(1, vertex_count, "vertex")?,
+                            line.index(2, vertex_count, "vertex")?,
+                        ],
+                        [
+                            line.index(3, normal_count, "normal")?,
+                            line.index(4, normal_count, "normal")?,
+                            line.index(5, normal_count, "normal")?,
+                        ],
+                    );
+                    if line.len() == 9 {
+                        pending_mesh.add_texcoords(
+                            &texcoords,
+                            texcoord_indices(&line, 6, texcoords.len())?,
+                        );
                     }
-                    let norm_indices: Vec<usize> = parse_full_slice(&line[4..]);
-                    for t in &norm_indices {
-                        assert!(t < &normals.len());
+                }
+                "texcoord" => {
+                    line.expect_arguments(2)?;
+                    let float_tokens = line.floats(0..2)?;
+                    texcoords.push((float_tokens[0], float_tokens[1]));
+                }
+                "fresnel" => {
+                    line.expect_arguments(1)?;
+                    let fresnel = line.parse_argument::<Fresnel>(
+                        0,
+                        "a Fresnel mode (off, schlick or exact)",
+                    )?;
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    current_material.fresnel = fresnel;
+                }
+                "emission" => {
+                    line.expect_one_of(&[3, 4])?;
+                    let float_tokens = line.floats(0..line.len())?;
+                    if let Some(index) =
+                        float_tokens.iter().position(|&value| value < 0.0)
+                    {
+                        return Err(line.argument_error(
+                            index,
+                            String::from("emission can't be negative"),
+                        ));
                     }
-                    scene.objects.push(Box::new(Triangle::new(
-                        current_material.clone(),
-                        vertices[vert_indices[0]],
-                        vertices[vert_indices[1]],
-                        vertices[vert_indices[2]],
-                        normals[norm_indices[0]],
-                        normals[norm_indices[1]],
-                        normals[norm_indices[2]],
-                    )));
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    current_material.emission =
+                        Pixel::from_slice_unclamped(&float_tokens[..3]);
+                    current_material.emission_strength =
+                        float_tokens.get(3).cloned().unwrap_or(1.0);
+                }
+                "absorption" => {
+                    line.expect_arguments(3)?;
+                    let float_tokens = line.floats(0..3)?;
+                    if let Some(index) =
+                        float_tokens.iter().position(|&value| value < 0.0)
+                    {
+                        return Err(line.argument_error(
+                            index,
+                            String::from("absorption can't be negative"),
+                        ));
+                    }
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    current_material.absorption =
+                        Pixel::from_slice_unclamped(&float_tokens);
+                }
+                "texture" => {
+                    let channel = line.parse_argument::<ColorChannel>(
+                        0,
+                        "a material color (ambient, diffuse, specular or \
+                         transmissive)",
+                    )?;
+                    let texture = parse_texture(&line, resources, &mut images)?;
+                    // Triangles collected so far keep the untextured material
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    current_material.set_texture(channel, texture);
+                }
+                "obj" => {
+                    line.expect_arguments(1)?;
+                    let file_name = line.argument(0)?;
+                    let mesh = resources
+                        .load_text(file_name)
+                        .and_then(|text| {
+                            ObjMesh::parse(&text).map_err(|err| {
+                                format!("{}: {}", file_name, err)
+                            })
+                        })
+                        .map_err(|message| line.argument_error(0, message))?;
+                    if !mesh.triangles.is_empty() {
+                        placement.add(
+                            &mut scene,
+                            Box::new(TriangleMesh::from_obj(
+                                current_material.clone(),
+                                mesh,
+                            )),
+                        );
+                    }
+                }
+                "push_transform" => {
+                    line.expect_arguments(0)?;
+                    placement.saved_transforms.push(placement.transform);
+                }
+                "pop_transform" => {
+                    line.expect_arguments(0)?;
+                    let transform =
+                        placement.saved_transforms.pop().ok_or_else(|| {
+                            line.error(String::from(
+                                "no transform saved by push_transform",
+                            ))
+                        })?;
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    placement.transform = transform;
+                }
+                "translate" => {
+                    line.expect_arguments(3)?;
+                    let offset = Vector3::from(line.floats(0..3)?.as_slice());
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let transform =
+                        placement.transform * Matrix4::translation(offset);
+                    placement.set_transform(&line, transform)?;
+                }
+                "scale" => {
+                    line.expect_one_of(&[1, 3])?;
+                    let float_tokens = line.floats(0..line.len())?;
+                    let factors = match float_tokens.len() {
+                        1 => Vector3::new(
+                            float_tokens[0],
+                            float_tokens[0],
+                            float_tokens[0],
+                        ),
+                        _ => Vector3::from(float_tokens.as_slice()),
+                    };
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let transform =
+                        placement.transform * Matrix4::scaling(factors);
+                    placement.set_transform(&line, transform)?;
+                }
+                "rotate" => {
+                    line.expect_arguments(4)?;
+                    let float_tokens = line.floats(0..4)?;
+                    let axis = Vector3::from(&float_tokens[1..4]);
+                    if axis.length() == 0.0 {
+                        return Err(line.argument_error(
+                            1,
+                            String::from("rotation axis can't be zero"),
+                        ));
+                    }
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let transform = placement.transform
+                        * Matrix4::rotation(axis, float_tokens[0].to_radians());
+                    placement.set_transform(&line, transform)?;
+                }
+                "transform" => {
+                    line.expect_arguments(12)?;
+                    let float_tokens = line.floats(0..12)?;
+                    let mut rows = [[0.0; 4]; 3];
+                    for (row, values) in
+                        rows.iter_mut().zip(float_tokens.chunks(4))
+                    {
+                        row.copy_from_slice(values);
+                    }
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let transform =
+                        placement.transform * Matrix4::from_rows(rows);
+                    placement.set_transform(&line, transform)?;
+                }
+                "begin_object" => {
+                    line.expect_arguments(1)?;
+                    if placement.definition.is_some() {
+                        return Err(line.error(String::from(
+                            "object definitions can't be nested",
+                        )));
+                    }
+                    if !placement.csg_groups.is_empty() {
+                        return Err(line.error(String::from(
+                            "object definitions can't start inside a CSG \
+                             group",
+                        )));
+                    }
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    placement.definition =
+                        Some((line.argument(0)?.to_string(), Vec::new()));
+                }
+                "end_object" => {
+                    line.expect_arguments(0)?;
+                    if !placement.csg_groups.is_empty() {
+                        return Err(line.error(String::from(
+                            "CSG group is missing its end_csg",
+                        )));
+                    }
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let (name, objects) =
+                        placement.definition.take().ok_or_else(|| {
+                            line.error(String::from(
+                                "no object definition started by \
+                                 begin_object",
+                            ))
+                        })?;
+                    placement.definitions.insert(name, objects);
                 }
-                _ => (),
+                "instance" => {
+                    line.expect_arguments(1)?;
+                    let objects = placement
+                        .definitions
+                        .get(line.argument(0)?)
+                        .cloned()
+                        .ok_or_else(|| {
+                            line.argument_error(
+                                0,
+                                String::from(
+                                    "no object defined with this name",
+                                ),
+                            )
+                        })?;
+                    for object in objects {
+                        let instance =
+                            placement.instance(object, placement.transform);
+                        placement.place(&mut scene, Box::new(instance));
+                    }
+                }
+                "csg" => {
+                    line.expect_arguments(1)?;
+                    let operation = line.parse_argument::<CsgOperation>(
+                        0,
+                        "a CSG operation (union, intersection or difference)",
+                    )?;
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    placement.csg_groups.push((operation, Vec::new()));
+                }
+                "sdf" => {
+                    let operation = match line.argument(0)? {
+                        "smooth_union"
+                        | "smooth_intersection"
+                        | "smooth_subtraction" => {
+                            line.expect_arguments(2)?;
+                            let k = line.floats(1..2)?[0];
+                            if k < 0.0 {
+                                return Err(line.argument_error(
+                                    1,
+                                    String::from("blend can't be negative"),
+                                ));
+                            }
+                            match line.argument(0)? {
+                                "smooth_union" => SdfOperation::SmoothUnion(k),
+                                "smooth_intersection" => {
+                                    SdfOperation::SmoothIntersection(k)
+                                }
+                                _ => SdfOperation::SmoothSubtraction(k),
+                            }
+                        }
+                        "repeat" => {
+                            line.expect_arguments(4)?;
+                            let period = Vector3::from(&line.floats(1..4)?[..]);
+                            if period.x < 0.0
+                                || period.y < 0.0
+                                || period.z < 0.0
+                            {
+                                return Err(line.error(String::from(
+                                    "repeat periods can't be negative",
+                                )));
+                            }
+                            SdfOperation::Repeat(period)
+                        }
+                        _ => {
+                            return Err(line.argument_error(
+                                0,
+                                String::from(
+                                    "expected an SDF operation \
+                                     (smooth_union, smooth_intersection, \
+                                     smooth_subtraction or repeat)",
+                                ),
+                            ))
+                        }
+                    };
+                    sdf_groups.push((operation, Vec::new()));
+                }
+                "sdf_sphere" | "sdf_box" | "sdf_torus" | "sdf_capsule" => {
+                    let shape = parse_sdf_shape(&line)?;
+                    match sdf_groups.last_mut() {
+                        Some((_, shapes)) => shapes.push(shape),
+                        None => {
+                            return Err(line.error(String::from(
+                                "SDF shapes must be inside an sdf group",
+                            )))
+                        }
+                    }
+                }
+                "end_sdf" => {
+                    line.expect_arguments(0)?;
+                    let (operation, shapes) =
+                        sdf_groups.pop().ok_or_else(|| {
+                            line.error(String::from(
+                                "no SDF group started by sdf",
+                            ))
+                        })?;
+                    if shapes.is_empty() {
+                        return Err(
+                            line.error(String::from("SDF group is empty"))
+                        );
+                    }
+                    let shape = operation.combine(shapes);
+                    // Groups inside groups are just another shape
+                    match sdf_groups.last_mut() {
+                        Some((_, shapes)) => shapes.push(shape),
+                        None => {
+                            flush_mesh(
+                                &mut scene,
+                                &mut placement,
+                                &mut pending_mesh,
+                                &current_material,
+                            );
+                            placement.add(
+                                &mut scene,
+                                Box::new(Sdf::new(
+                                    shape,
+                                    current_material.clone(),
+                                )),
+                            );
+                        }
+                    }
+                }
+                "end_csg" => {
+                    line.expect_arguments(0)?;
+                    flush_mesh(
+                        &mut scene,
+                        &mut placement,
+                        &mut pending_mesh,
+                        &current_material,
+                    );
+                    let (operation, parts) =
+                        placement.csg_groups.pop().ok_or_else(|| {
+                            line.error(String::from(
+                                "no CSG group started by csg",
+                            ))
+                        })?;
+                    if parts.len() < 2 {
+                        return Err(line.error(format!(
+                            "CSG group needs at least 2 objects, but has {}",
+                            parts.len()
+                        )));
+                    }
+                    // More than two parts combine from the first onwards,
+                    // e.g. a difference takes all the others from the first
+                    let mut parts = parts.into_iter();
+                    let first = parts.next().expect("checked above");
+                    let combined = parts.fold(first, |left, right| {
+                        Box::new(Csg::new(
+                            operation,
+                            left,
+                            right,
+                            current_material.clone(),
+                        ))
+                    });
+                    placement.place(&mut scene, combined);
+                }
+                _ => warn!(
+                    "Ignoring unknown directive `{}` on line {}",
+                    line.name, line.line
+                ),
+            }
+        }
+
+        flush_mesh(
+            &mut scene,
+            &mut placement,
+            &mut pending_mesh,
+            &current_material,
+        );
+        if !sdf_groups.is_empty() {
+            return Err(SceneError::new(
+                0,
+                "sdf",
+                String::from("SDF group is missing its end_sdf"),
+            ));
+        }
+        if !placement.csg_groups.is_empty() {
+            return Err(SceneError::new(
+                0,
+                "csg",
+                String::from("CSG group is missing its end_csg"),
+            ));
+        }
+        if let Some((name, _)) = placement.definition {
+            return Err(SceneError::new(
+                0,
+                "begin_object",
+                format!("object `{}` is missing its end_object", name),
+            ));
+        }
+        // Glowing objects also light the rest of the scene
+        let emitters: Vec<_> = scene
+            .objects
+            .iter()
+            .map(|object| object.light(DEFAULT_LIGHT_SAMPLES))
+            .collect();
+        scene.light_objects =
+            emitters.iter().map(|light| light.is_some()).collect();
+        scene.lights.extend(emitters.into_iter().flatten());
+        scene.build_bvh();
+
+        debug!("Loaded scene:\n{:#?}", scene);
+        Ok(scene)
+    }
+
+    /// Change the resolution of the output image, keeping the camera's
+    /// horizontal field of view if it has one
+    pub fn set_resolution(&mut self, resolution: (usize, usize)) {
+        self.resolution = resolution;
+        let aspect = self.aspect();
+        self.camera.fit_aspect(aspect);
+    }
+
+    /// How many times wider than high the output image is
+    fn aspect(&self) -> f64 {
+        self.resolution.0 as f64 / self.resolution.1 as f64
+    }
+
+    /// (Re)build the acceleration structure over `objects`. Must be called
+    /// after objects are added or removed.
+    pub fn build_bvh(&mut self) {
+        let mut bounded = Vec::new();
+        self.unbounded_objects.clear();
+        for (index, object) in self.objects.iter().enumerate() {
+            match object.bounding_box() {
+                Some(bounds) => bounded.push((index, bounds)),
+                None => self.unbounded_objects.push(index),
+            }
+        }
+        self.bvh = Bvh::build(&bounded);
+    }
+
+    /// What a ray that hits nothing sees, looking along `direction`
+    pub fn background_color(&self, direction: Vector3) -> Pixel {
+        match self.environment {
+            Some(ref environment) => environment.color(direction),
+            None => self.background,
+        }
+    }
+
+    /// Find the closest object the ray hits, and where it hits it
+    pub fn closest_intersection(
+        &self,
+        ray: &Ray,
+    ) -> Option<(&Object, Intersection)> {
+        self.closest_hit(ray).map(|(index, intersection)| {
+            (self.objects[index].as_ref(), intersection)
+        })
+    }
+
+    /// Like `closest_intersection`, but gives the index of the object hit
+    pub fn closest_hit(&self, ray: &Ray) -> Option<(usize, Intersection)> {
+        let mut closest = self
+            .bvh
+            .closest(ray, |index, ray| self.objects[index].intersects(ray));
+
+        for &index in &self.unbounded_objects {
+            if let Some(intersection) = self.objects[index].intersects(ray) {
+                let distance = (intersection.point - ray.start).length();
+                let is_closer = match closest {
+                    Some((_, ref c)) => {
+                        distance < (c.point - ray.start).length()
+                    }
+                    None => true,
+                };
+                if is_closer {
+                    closest = Some((index, intersection));
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Check if the ray hits anything at all (e.g. for shadow rays)
+    pub fn any_intersection(&self, ray: &Ray) -> bool {
+        self.bvh.any(ray, |index, ray| {
+            self.objects[index].intersects(ray).is_some()
+        }) || self
+            .unbounded_objects
+            .iter()
+            .any(|&index| self.objects[index].intersects(ray).is_some())
+    }
+}
+
+/// Load the PNG image named by an argument, or reuse it if it has already
+/// been loaded
+fn load_image(
+    line: &Directive,
+    index: usize,
+    resources: &Resources,
+    images: &mut HashMap<String, Arc<Image>>,
+) -> Result<Arc<Image>, SceneError> {
+    let file_name = line.argument(index)?;
+    if let Some(image) = images.get(file_name) {
+        return Ok(image.clone());
+    }
+    let image = resources
+        .load(file_name)
+        .and_then(|bytes| {
+            Image::from_png_bytes(&bytes)
+                .map_err(|err| format!("{}: {}", file_name, err))
+        })
+        .map_err(|message| line.argument_error(index, message))?;
+    let image = Arc::new(image);
+    images.insert(file_name.to_string(), image.clone());
+    Ok(image)
+}
+
+/// Parse the texture of a `texture` directive:
+///
+/// `texture <color> image <file> [nearest|bilinear] [repeat|clamp]`
+///
+/// `texture <color> <pattern> <r g b> <r g b> <scale> [world|object|uv]`
+fn parse_texture(
+    line: &Directive,
+    resources: &Resources,
+    images: &mut HashMap<String, Arc<Image>>,
+) -> Result<Arc<Texture>, SceneError> {
+    if line.argument(1)? == "image" {
+        line.expect_one_of(&[3, 4, 5])?;
+        let filter = if line.len() > 3 {
+            line.parse_argument::<TextureFilter>(
+                3,
+                "a texture filter (nearest or bilinear)",
+            )?
+        } else {
+            TextureFilter::Bilinear
+        };
+        let wrap = if line.len() > 4 {
+            line.parse_argument::<WrapMode>(4, "a wrap mode (repeat or clamp)")?
+        } else {
+            WrapMode::Repeat
+        };
+        let image = load_image(line, 2, resources, images)?;
+        return Ok(Arc::new(ImageTexture::new(image, filter, wrap)));
+    }
+
+    let pattern = line.parse_argument::<Pattern>(
+        1,
+        "a texture type (image, checker, stripes, noise, marble or wood)",
+    )?;
+    line.expect_one_of(&[9, 10])?;
+    let float_tokens = line.floats(2..9)?;
+    let scale = float_tokens[6];
+    if scale <= 0.0 {
+        return Err(
+            line.argument_error(8, String::from("scale must be positive"))
+        );
+    }
+    let space = if line.len() == 10 {
+        line.parse_argument::<TextureSpace>(
+            9,
+            "a texture space (world, object or uv)",
+        )?
+    } else {
+        TextureSpace::Object
+    };
+    Ok(Arc::new(ProceduralTexture::new(
+        pattern,
+        Pixel::from(&float_tokens[0..3]),
+        Pixel::from(&float_tokens[3..6]),
+        scale,
+        space,
+    )))
+}
+
+// Shadow rays per intersection for area lights, unless the scene says
+// otherwise
+const DEFAULT_LIGHT_SAMPLES: usize = 16;
+
+/// The optional sample count of an area light, at argument `index`
+fn light_samples(line: &Directive, index: usize) -> Result<usize, SceneError> {
+    if line.len() <= index {
+        return Ok(DEFAULT_LIGHT_SAMPLES);
+    }
+    let samples = line.parse_argument::<usize>(index, "a positive integer")?;
+    if samples == 0 {
+        return Err(line.argument_error(
+            index,
+            String::from("at least 1 sample is needed"),
+        ));
+    }
+    Ok(samples)
+}
+
+/// Check that a size (like a radius) is positive
+fn positive(
+    line: &Directive,
+    index: usize,
+    value: f64,
+) -> Result<f64, SceneError> {
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(line.argument_error(index, String::from("must be positive")))
+    }
+}
+
+/// Whether the ends of a cylinder or cone are closed, from the argument at
+/// `index`
+fn parse_caps(line: &Directive, index: usize) -> Result<bool, SceneError> {
+    match line.argument(index)? {
+        "capped" => Ok(true),
+        "open" => Ok(false),
+        _ => {
+            Err(line
+                .argument_error(index, String::from("expected capped or open")))
+        }
+    }
+}
+
+/// One of the `sdf_*` primitives
+fn parse_sdf_shape(line: &Directive) -> Result<SdfShape, SceneError> {
+    match line.name {
+        "sdf_sphere" => {
+            line.expect_arguments(4)?;
+            let float_tokens = line.floats(0..4)?;
+            Ok(SdfShape::Sphere {
+                center: Vector3::from(&float_tokens[..3]),
+                radius: positive(line, 3, float_tokens[3])?,
+            })
+        }
+        "sdf_box" => {
+            line.expect_one_of(&[6, 7])?;
+            let float_tokens = line.floats(0..line.len())?;
+            let half_size = Vector3::from(&float_tokens[3..6]);
+            for axis in 0..3 {
+                positive(line, axis + 3, half_size.axis(axis))?;
+            }
+            let rounding = float_tokens.get(6).cloned().unwrap_or(0.0);
+            let smallest = half_size.x.min(half_size.y).min(half_size.z);
+            if rounding < 0.0 || rounding > smallest {
+                return Err(line.argument_error(
+                    6,
+                    String::from(
+                        "rounding must be between 0 and the smallest half size",
+                    ),
+                ));
             }
+            Ok(SdfShape::Box {
+                center: Vector3::from(&float_tokens[..3]),
+                half_size,
+                rounding,
+            })
         }
+        "sdf_torus" => {
+            line.expect_arguments(5)?;
+            let float_tokens = line.floats(0..5)?;
+            Ok(SdfShape::Torus {
+                center: Vector3::from(&float_tokens[..3]),
+                major_radius: positive(line, 3, float_tokens[3])?,
+                minor_radius: positive(line, 4, float_tokens[4])?,
+            })
+        }
+        _ => {
+            line.expect_arguments(7)?;
+            let float_tokens = line.floats(0..7)?;
+            Ok(SdfShape::Capsule {
+                start: Vector3::from(&float_tokens[..3]),
+                end: Vector3::from(&float_tokens[3..6]),
+                radius: positive(line, 6, float_tokens[6])?,
+            })
+        }
+    }
+}
+
+/// Three texture coordinate indices, starting at argument `first`
+fn texcoord_indices(
+    line: &Directive,
+    first: usize,
+    count: usize,
+) -> Result<[usize; 3], SceneError> {
+    Ok([
+        line.index(first, count, "texture coordinate")?,
+        line.index(first + 1, count, "texture coordinate")?,
+        line.index(first + 2, count, "texture coordinate")?,
+    ])
+}
 
-        println!("Loaded scene:\n{:#?}", scene);
-        scene
+/// Add the triangles collected so far as one mesh object
+fn flush_mesh(
+    scene: &mut Scene,
+    placement: &mut Placement,
+    pending_mesh: &mut TriangleMeshBuilder,
+    material: &Material,
+) {
+    if !pending_mesh.is_empty() {
+        placement.add(scene, Box::new(pending_mesh.build(material.clone())));
     }
 }
 
-fn parse_full_slice<T: FromStr + Default>(str_slice: &[&str]) -> Vec<T> {
-    str_slice
-        .iter()
-        .map(|&s| s.parse::<T>().unwrap_or_default())
-        .collect()
+/// Where objects go as they are parsed: into the scene, moved by the
+/// current transform, into a CSG group, or into an object definition for
+/// `instance` to place
+#[derive(Default)]
+struct Placement {
+    transform: Matrix4,
+    /// Transforms saved by `push_transform`
+    saved_transforms: Vec<Matrix4>,
+    /// The definition between `begin_object` and `end_object`, if any
+    definition: Option<(String, Vec<Arc<Object>>)>,
+    /// Finished definitions, by name
+    definitions: HashMap<String, Vec<Arc<Object>>>,
+    /// Open `csg` groups and the parts collected so far, innermost last
+    csg_groups: Vec<(CsgOperation, Vec<Box<Object>>)>,
+}
+
+impl Placement {
+    /// Place a newly parsed object, moved by the current transform
+    fn add(&mut self, scene: &mut Scene, object: Box<Object>) {
+        let object = if self.transform.is_identity() {
+            object
+        } else {
+            Box::new(self.instance(Arc::from(object), self.transform))
+        };
+        self.place(scene, object);
+    }
+
+    /// Place an object that is already where it should be
+    fn place(&mut self, scene: &mut Scene, object: Box<Object>) {
+        if let Some((_, parts)) = self.csg_groups.last_mut() {
+            parts.push(object);
+            return;
+        }
+        match self.definition {
+            Some((_, ref mut objects)) => objects.push(Arc::from(object)),
+            None => scene.objects.push(object),
+        }
+    }
+
+    /// Change the transform, making sure it stays invertible
+    fn set_transform(
+        &mut self,
+        line: &Directive,
+        transform: Matrix4,
+    ) -> Result<(), SceneError> {
+        if transform.inverse().is_none() {
+            return Err(line.error(String::from(
+                "transform squashes objects flat, so it can't be undone",
+            )));
+        }
+        self.transform = transform;
+        Ok(())
+    }
+
+    fn instance(&self, object: Arc<Object>, transform: Matrix4) -> Instance {
+        Instance::new(object, transform)
+            .expect("transforms are checked to be invertible")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_error(scene: &str) -> SceneError {
+        Scene::from_text(scene.to_string()).unwrap_err()
+    }
+
+    #[test]
+    fn wrong_arity() {
+        let err = load_error("# comment\nsphere 0 0 0 1 2\n");
+        assert_eq!(err.line, 2);
+        assert_eq!(err.directive, "sphere");
+        assert_eq!(err.column, Some(16));
+        assert_eq!(err.token, Some(String::from("2")));
+    }
+
+    #[test]
+    fn bad_number() {
+        let err = load_error("background 0 0.5.1 0\n");
+        assert_eq!(err.line, 1);
+        assert_eq!(err.column, Some(14));
+        assert_eq!(err.token, Some(String::from("0.5.1")));
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let err = load_error(
+            "max_vertices 3\nvertex 0 0 0\nvertex 1 0 0\ntriangle 0 1 2\n",
+        );
+        assert_eq!(err.line, 4);
+        assert_eq!(err.directive, "triangle");
+        assert_eq!(err.token, Some(String::from("2")));
+    }
+
+    #[test]
+    fn max_vertices_twice() {
+        // Growing keeps the vertices so far and makes room for more
+        let scene = Scene::from_text(String::from(
+            "max_vertices 3\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\n\
+             max_vertices 4\nvertex 1 1 0\ntriangle 1 3 2\n",
+        ))
+        .unwrap();
+        assert_eq!(scene.objects.len(), 1);
+
+        // Shrinking drops the vertices past the new size
+        let err = load_error(
+            "max_vertices 3\nvertex 0 0 0\nvertex 1 0 0\nvertex 0 1 0\n\
+             max_vertices 1\ntriangle 0 1 2\n",
+        );
+        assert_eq!(err.line, 6);
+        assert_eq!(err.directive, "triangle");
+        assert_eq!(err.token, Some(String::from("1")));
+    }
+
+    #[test]
+    fn vertex_before_max_vertices() {
+        let err = load_error("vertex 0 0 0\n");
+        assert_eq!(err.line, 1);
+        assert_eq!(err.directive, "vertex");
+        assert_eq!(err.column, None);
+    }
+
+    #[test]
+    fn pixel_filter() {
+        let scene =
+            Scene::from_text(String::from("pixel_filter tent 1.5\n")).unwrap();
+        assert_eq!(scene.sampler.filter_radius, 1.5);
+        let err = load_error("pixel_filter tent 0\n");
+        assert_eq!(err.token, Some(String::from("0")));
+        let err = load_error("pixel_filter tent NaN\n");
+        assert_eq!(err.token, Some(String::from("NaN")));
+    }
+
+    #[test]
+    fn fisheye_field_of_view() {
+        assert!(
+            Scene::from_text(String::from("projection fisheye 360\n")).is_ok()
+        );
+        let err = load_error("projection fisheye 400\n");
+        assert_eq!(err.token, Some(String::from("400")));
+        let err = load_error("projection fisheye NaN\n");
+        assert_eq!(err.token, Some(String::from("NaN")));
+    }
+
+    #[test]
+    fn look_at() {
+        let err = load_error("look_at 0 0 0  0 5 0  0 1 0  40\n");
+        assert_eq!(err.directive, "look_at");
+        let err = load_error("look_at 0 0 0  0 0 1  0 1 0  NaN\n");
+        assert_eq!(err.token, Some(String::from("NaN")));
+
+        // 90 degrees across a 2:1 image is about 53 degrees down it
+        let mut scene = Scene::from_text(String::from(
+            "look_at 0 0 0  0 0 1  0 1 0  90 horizontal\n\
+             film_resolution 200 100\n",
+        ))
+        .unwrap();
+        let vertical = scene.camera.vert_half_angle.to_degrees() * 2.0;
+        assert!((vertical - 53.130102354).abs() < 1e-6, "{}", vertical);
+
+        // Still 90 degrees across after changing the aspect ratio
+        scene.set_resolution((100, 200));
+        let horizontal = (scene.camera.vert_half_angle.tan() * 0.5)
+            .atan()
+            .to_degrees()
+            * 2.0;
+        assert!((horizontal - 90.0).abs() < 1e-6, "{}", horizontal);
+    }
+
+    #[test]
+    fn nested_csg() {
+        let scene = Scene::from_text(String::from(
+            "csg difference\n\
+             box -1 -1 -1  1 1 1\n\
+             csg union\n\
+             cylinder 0 -2 0  0 2 0  .5 capped\n\
+             sphere 0 0 0 1.2\n\
+             end_csg\n\
+             end_csg\n",
+        ))
+        .unwrap();
+        assert_eq!(scene.objects.len(), 1);
+
+        let err = load_error("csg union\nsphere 0 0 0 1\nend_csg\n");
+        assert_eq!(err.line, 3);
+        let err = load_error("csg union\nsphere 0 0 0 1\nsphere 1 0 0 1\n");
+        assert_eq!(err.directive, "csg");
+    }
+
+    #[test]
+    fn sdf_groups() {
+        let scene = Scene::from_text(String::from(
+            "sdf smooth_union .5\n\
+             sdf_sphere 0 0 0 1\n\
+             sdf repeat 2 0 0\n\
+             sdf_box 0 0 0 .5 .5 .5 .1\n\
+             end_sdf\n\
+             end_sdf\n",
+        ))
+        .unwrap();
+        assert_eq!(scene.objects.len(), 1);
+
+        let err = load_error("sdf_sphere 0 0 0 1\n");
+        assert_eq!(err.directive, "sdf_sphere");
+        let err = load_error("sdf smooth_union .5\nsdf_box 0 0 0 1 1 1 2\n");
+        assert_eq!(err.token, Some(String::from("2")));
+    }
 }
@@ -0,0 +1,65 @@
+//! Errors encountered while loading a scene
+
+use std::error::Error;
+use std::fmt;
+
+/// Describes what went wrong while loading a scene, and where
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneError {
+    /// Line in the scene file (1-based), or 0 if the error isn't tied to a
+    /// particular line (e.g. the file couldn't be read)
+    pub line: usize,
+
+    /// Column of the offending token (1-based), if there is one
+    pub column: Option<usize>,
+
+    /// The offending token, if there is one
+    pub token: Option<String>,
+
+    /// The directive being parsed (e.g. `camera`)
+    pub directive: String,
+
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+impl SceneError {
+    pub fn new(line: usize, directive: &str, message: String) -> Self {
+        Self {
+            line,
+            column: None,
+            token: None,
+            directive: directive.to_string(),
+            message,
+        }
+    }
+
+    /// Point the error at a particular token on the line
+    pub fn at_token(mut self, column: usize, token: &str) -> Self {
+        self.column = Some(column);
+        self.token = Some(token.to_string());
+        self
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "line {}", self.line)?;
+            if let Some(column) = self.column {
+                write!(f, ", column {}", column)?;
+            }
+            write!(f, ": ")?;
+        }
+        if !self.directive.is_empty() {
+            write!(f, "{}: ", self.directive)?;
+        }
+        write!(f, "{}", self.message)?;
+        if let Some(ref token) = self.token {
+            write!(f, " (found `{}`)", token)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for SceneError {}
@@ -0,0 +1,178 @@
+//! A texture read from an image, mapped onto a surface with its UV
+//! coordinates
+//!
+//! (0, 0) is the bottom left corner of the image and (1, 1) the top right.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::intersection::Intersection;
+use crate::pixel::Pixel;
+use crate::textures::texture::Texture;
+
+/// How to pick a color between texel centers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureFilter {
+    /// The closest texel
+    Nearest,
+    /// Blend the four closest texels
+    Bilinear,
+}
+
+impl FromStr for TextureFilter {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nearest" => Ok(TextureFilter::Nearest),
+            "bilinear" | "linear" => Ok(TextureFilter::Bilinear),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What to do with UV coordinates outside of [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    /// Tile the image
+    Repeat,
+    /// Stretch the edge texels
+    Clamp,
+}
+
+impl FromStr for WrapMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "repeat" | "wrap" => Ok(WrapMode::Repeat),
+            "clamp" => Ok(WrapMode::Clamp),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ImageTexture {
+    /// Shared, since the same file is often used by several materials
+    pub image: Arc<Image>,
+    pub filter: TextureFilter,
+    pub wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(
+        image: Arc<Image>,
+        filter: TextureFilter,
+        wrap: WrapMode,
+    ) -> Self {
+        Self {
+            image,
+            filter,
+            wrap,
+        }
+    }
+
+    /// Color at a UV coordinate
+    pub fn sample(&self, u: f64, v: f64) -> Pixel {
+        // Continuous texel coordinates, with texel centers at .5
+        let x = u * self.image.width as f64 - 0.5;
+        let y = (1.0 - v) * self.image.height as f64 - 0.5;
+        match self.filter {
+            TextureFilter::Nearest => {
+                self.texel(x.round() as i64, y.round() as i64)
+            }
+            TextureFilter::Bilinear => {
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = lerp(self.texel(x0, y0), self.texel(x0 + 1, y0), fx);
+                let bottom = lerp(
+                    self.texel(x0, y0 + 1),
+                    self.texel(x0 + 1, y0 + 1),
+                    fx,
+                );
+                lerp(top, bottom, fy)
+            }
+        }
+    }
+
+    /// A single texel, with the coordinates wrapped or clamped
+    fn texel(&self, x: i64, y: i64) -> Pixel {
+        let col = self.wrap_coordinate(x, self.image.width);
+        let row = self.wrap_coordinate(y, self.image.height);
+        self.image
+            .get_pixel(row, col)
+            .unwrap_or_else(|| Pixel::from_rgb(0.0, 0.0, 0.0))
+    }
+
+    fn wrap_coordinate(&self, coordinate: i64, size: usize) -> usize {
+        let size = size as i64;
+        match self.wrap {
+            WrapMode::Repeat => coordinate.rem_euclid(size) as usize,
+            WrapMode::Clamp => coordinate.max(0).min(size - 1) as usize,
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn color_at(&self, intersection: &Intersection) -> Pixel {
+        let (u, v) = intersection.uv;
+        self.sample(u, v)
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "ImageTexture: {}x{} {:?} {:?}",
+            self.image.width, self.image.height, self.filter, self.wrap
+        )
+    }
+}
+
+/// Unclamped linear interpolation, keeping alpha
+fn lerp(a: Pixel, b: Pixel, amount: f64) -> Pixel {
+    Pixel::from_rgba(
+        a.r + (b.r - a.r) * amount,
+        a.g + (b.g - a.g) * amount,
+        a.b + (b.b - a.b) * amount,
+        a.a + (b.a - a.a) * amount,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x1 image: black on the left, white on the right
+    fn texture(filter: TextureFilter, wrap: WrapMode) -> ImageTexture {
+        let mut image = Image::new(2, 1);
+        image.set_pixel(0, 0, Pixel::from_rgb(0.0, 0.0, 0.0));
+        image.set_pixel(0, 1, Pixel::from_rgb(1.0, 1.0, 1.0));
+        ImageTexture::new(Arc::new(image), filter, wrap)
+    }
+
+    #[test]
+    fn nearest_and_bilinear() {
+        let nearest = texture(TextureFilter::Nearest, WrapMode::Clamp);
+        assert_eq!(nearest.sample(0.3, 0.5).r, 0.0);
+        assert_eq!(nearest.sample(0.7, 0.5).r, 1.0);
+
+        let bilinear = texture(TextureFilter::Bilinear, WrapMode::Clamp);
+        // Halfway between the two texel centers
+        assert!((bilinear.sample(0.5, 0.5).r - 0.5).abs() < 1e-9);
+        // Clamped past the last texel center
+        assert_eq!(bilinear.sample(0.9, 0.5).r, 1.0);
+    }
+
+    #[test]
+    fn repeat_and_clamp() {
+        let repeat = texture(TextureFilter::Nearest, WrapMode::Repeat);
+        assert_eq!(repeat.sample(1.3, 0.5).r, 0.0);
+        assert_eq!(repeat.sample(-0.3, 0.5).r, 1.0);
+
+        let clamp = texture(TextureFilter::Nearest, WrapMode::Clamp);
+        assert_eq!(clamp.sample(1.3, 0.5).r, 1.0);
+        assert_eq!(clamp.sample(-0.3, 0.5).r, 0.0);
+    }
+}
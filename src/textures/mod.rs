@@ -0,0 +1,5 @@
+//! Textures that give materials colors that vary over a surface
+pub mod image_texture;
+pub mod perlin;
+pub mod procedural;
+pub mod texture;
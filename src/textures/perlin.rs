@@ -0,0 +1,140 @@
+//! Perlin noise ("Improving Noise", Ken Perlin 2002)
+
+use crate::random::Rng;
+use crate::vector::Vector3;
+
+const TABLE_SIZE: usize = 256;
+
+pub struct Perlin {
+    /// A shuffled permutation of 0..256, repeated twice so lookups never
+    /// need to wrap
+    permutation: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng::new(seed);
+        let mut table: Vec<usize> = (0..TABLE_SIZE).collect();
+        // Fisher-Yates shuffle
+        for i in (1..TABLE_SIZE).rev() {
+            let j = (rng.next_f64() * (i + 1) as f64) as usize;
+            table.swap(i, j);
+        }
+        let permutation = table.iter().chain(table.iter()).cloned().collect();
+        Self { permutation }
+    }
+
+    /// Smooth noise in roughly [-1, 1], 0 at every integer point
+    pub fn noise(&self, point: Vector3) -> f64 {
+        let floor = |value: f64| value.floor();
+        let cell = |value: f64| (floor(value) as i64 & 255) as usize;
+        let (xi, yi, zi) = (cell(point.x), cell(point.y), cell(point.z));
+        let x = point.x - floor(point.x);
+        let y = point.y - floor(point.y);
+        let z = point.z - floor(point.z);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+
+        let p = &self.permutation;
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(u, grad(p[aa], x, y, z), grad(p[ba], x - 1.0, y, z)),
+                lerp(
+                    u,
+                    grad(p[ab], x, y - 1.0, z),
+                    grad(p[bb], x - 1.0, y - 1.0, z),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    grad(p[aa + 1], x, y, z - 1.0),
+                    grad(p[ba + 1], x - 1.0, y, z - 1.0),
+                ),
+                lerp(
+                    u,
+                    grad(p[ab + 1], x, y - 1.0, z - 1.0),
+                    grad(p[bb + 1], x - 1.0, y - 1.0, z - 1.0),
+                ),
+            ),
+        )
+    }
+
+    /// Sum of the absolute value of several octaves of noise, each at twice
+    /// the frequency and half the amplitude of the last
+    pub fn turbulence(&self, point: Vector3, octaves: usize) -> f64 {
+        let mut sum = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            sum += self.noise(point * frequency).abs() / frequency;
+            frequency *= 2.0;
+        }
+        sum
+    }
+
+    /// Like `turbulence`, but keeping the sign of each octave
+    pub fn fractal(&self, point: Vector3, octaves: usize) -> f64 {
+        let mut sum = 0.0;
+        let mut frequency = 1.0;
+        for _ in 0..octaves {
+            sum += self.noise(point * frequency) / frequency;
+            frequency *= 2.0;
+        }
+        sum
+    }
+}
+
+/// 6t^5 - 15t^4 + 10t^3, which has zero first and second derivatives at 0
+/// and 1
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(amount: f64, low: f64, high: f64) -> f64 {
+    low + amount * (high - low)
+}
+
+/// Dot product of (x, y, z) with one of 12 gradient directions
+fn grad(hash: usize, x: f64, y: f64, z: f64) -> f64 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = if h < 4 {
+        y
+    } else if h == 12 || h == 14 {
+        x
+    } else {
+        z
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_on_lattice_and_bounded() {
+        let perlin = Perlin::new(1);
+        assert_eq!(perlin.noise(Vector3::new(3.0, -2.0, 7.0)), 0.0);
+        let mut rng = Rng::new(5);
+        for _ in 0..1000 {
+            let point = Vector3::new(
+                rng.next_f64() * 20.0 - 10.0,
+                rng.next_f64() * 20.0 - 10.0,
+                rng.next_f64() * 20.0 - 10.0,
+            );
+            assert!(perlin.noise(point).abs() <= 1.1);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Textures computed from the hit point instead of read from an image
+//!
+//! Every pattern blends between two colors. Points are divided by `scale`
+//! first, so it sets the size of a checker square, a stripe, a marble vein
+//! or a wood ring.
+
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::intersection::Intersection;
+use crate::pixel::Pixel;
+use crate::textures::perlin::Perlin;
+use crate::textures::texture::{Texture, TextureSpace};
+use crate::vector::Vector3;
+
+// Keeps surfaces that lie exactly on a cell boundary (like a ground plane
+// at y = 0) from flickering between cells due to rounding
+const BOUNDARY_OFFSET: f64 = 1e-6;
+
+const NOISE_OCTAVES: usize = 6;
+const NOISE_SEED: u64 = 0x5eed;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Alternating cubes (squares on a surface)
+    Checker,
+    /// Alternating slabs along the x axis (u in UV space)
+    Stripes,
+    /// Smooth Perlin noise
+    Noise,
+    /// Stripes along x, distorted by turbulence
+    Marble,
+    /// Noisy rings around the y axis
+    Wood,
+}
+
+impl FromStr for Pattern {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "checker" | "checkerboard" => Ok(Pattern::Checker),
+            "stripes" => Ok(Pattern::Stripes),
+            "noise" | "perlin" => Ok(Pattern::Noise),
+            "marble" => Ok(Pattern::Marble),
+            "wood" => Ok(Pattern::Wood),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct ProceduralTexture {
+    pub pattern: Pattern,
+    pub color1: Pixel,
+    pub color2: Pixel,
+    pub scale: f64,
+    pub space: TextureSpace,
+    perlin: Perlin,
+}
+
+impl ProceduralTexture {
+    pub fn new(
+        pattern: Pattern,
+        color1: Pixel,
+        color2: Pixel,
+        scale: f64,
+        space: TextureSpace,
+    ) -> Self {
+        Self {
+            pattern,
+            color1,
+            color2,
+            scale,
+            space,
+            perlin: Perlin::new(NOISE_SEED),
+        }
+    }
+
+    /// How far between `color1` (0) and `color2` (1) a point is
+    pub fn blend(&self, point: Vector3) -> f64 {
+        let p = point * (1.0 / self.scale);
+        let cell = |value: f64| (value + BOUNDARY_OFFSET).floor() as i64;
+        match self.pattern {
+            Pattern::Checker => {
+                let sum = cell(p.x) + cell(p.y) + cell(p.z);
+                sum.rem_euclid(2) as f64
+            }
+            Pattern::Stripes => cell(p.x).rem_euclid(2) as f64,
+            Pattern::Noise => (0.5
+                + 0.5 * self.perlin.fractal(p, NOISE_OCTAVES))
+            .clamp(0.0, 1.0),
+            Pattern::Marble => {
+                let turbulence = self.perlin.turbulence(p, NOISE_OCTAVES);
+                0.5 + 0.5 * (PI * (p.x + 2.0 * turbulence)).sin()
+            }
+            Pattern::Wood => {
+                let radius = (p.x * p.x + p.z * p.z).sqrt()
+                    + 0.25 * self.perlin.noise(p * 2.0);
+                radius - radius.floor()
+            }
+        }
+    }
+}
+
+impl Texture for ProceduralTexture {
+    fn color_at(&self, intersection: &Intersection) -> Pixel {
+        let amount = self.blend(self.space.point(intersection));
+        self.color1.lerp(&self.color2, amount)
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "ProceduralTexture: {:?} {:?} scale {}",
+            self.pattern, self.space, self.scale
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(pattern: Pattern) -> ProceduralTexture {
+        ProceduralTexture::new(
+            pattern,
+            Pixel::from_rgb(0.0, 0.0, 0.0),
+            Pixel::from_rgb(1.0, 1.0, 1.0),
+            2.0,
+            TextureSpace::World,
+        )
+    }
+
+    #[test]
+    fn checker_alternates() {
+        let checker = texture(Pattern::Checker);
+        // A ground plane at y = 0, with 2 unit squares
+        assert_eq!(checker.blend(Vector3::new(0.5, 0.0, 0.5)), 0.0);
+        assert_eq!(checker.blend(Vector3::new(2.5, 0.0, 0.5)), 1.0);
+        assert_eq!(checker.blend(Vector3::new(-0.5, 0.0, 0.5)), 1.0);
+        assert_eq!(checker.blend(Vector3::new(2.5, -1e-12, 2.5)), 0.0);
+    }
+
+    #[test]
+    fn blends_stay_in_range() {
+        for &pattern in &[
+            Pattern::Stripes,
+            Pattern::Noise,
+            Pattern::Marble,
+            Pattern::Wood,
+        ] {
+            let texture = texture(pattern);
+            for i in 0..200 {
+                let t = f64::from(i) * 0.137;
+                let amount =
+                    texture.blend(Vector3::new(t, t * 0.5 - 3.0, -t * 0.3));
+                assert!((0.0..=1.0).contains(&amount), "{:?}", pattern);
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! A generic texture
+//!
+//! Looks up a color for a point on a surface.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::intersection::Intersection;
+use crate::pixel::Pixel;
+use crate::vector::Vector3;
+
+/// Textures are shared between render threads, so they must be `Send` and
+/// `Sync`
+pub trait Texture: Send + Sync {
+    /// Color of the texture where a ray hit a surface
+    fn color_at(&self, intersection: &Intersection) -> Pixel;
+
+    fn info(&self) -> String;
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.info())
+    }
+}
+
+/// Which coordinates a procedural texture is evaluated in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSpace {
+    /// The hit point in the scene
+    World,
+    /// The hit point relative to the object, so the texture moves with it
+    Object,
+    /// The surface's texture coordinates, as (u, v, 0)
+    Uv,
+}
+
+impl TextureSpace {
+    pub fn point(self, intersection: &Intersection) -> Vector3 {
+        match self {
+            TextureSpace::World => intersection.point,
+            TextureSpace::Object => intersection.local_point,
+            TextureSpace::Uv => {
+                Vector3::new(intersection.uv.0, intersection.uv.1, 0.0)
+            }
+        }
+    }
+}
+
+impl FromStr for TextureSpace {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "world" => Ok(TextureSpace::World),
+            "object" => Ok(TextureSpace::Object),
+            "uv" => Ok(TextureSpace::Uv),
+            _ => Err(()),
+        }
+    }
+}
@@ -110,23 +110,44 @@ impl Vector3 {
         self - (*normal * self.dot(normal)) * 2.0
     }
 
-    // Uses the same math from the GLM library - doesn't match GLM-rs
-    pub fn refract(self, normal: Self, eta: f64) -> Self {
-        // Figure out if we're going into or coming out of the material
-        let dot_ni = self.dot(&normal);
-        let eta = if dot_ni > 0.0 { eta } else { 1.0 / eta };
+    /// Refract through a surface with index of refraction `ior`, going in
+    /// if `self` points against `normal` and out otherwise. `None` means
+    /// total internal reflection.
+    // Uses the same math as GLM's refract
+    pub fn refract(self, normal: Self, ior: f64) -> Option<Self> {
+        // Figure out if we're going into or coming out of the material, and
+        // make the normal face the incoming ray
+        let cos_incident = -self.dot(&normal);
+        let (eta, normal, cos_incident) = if cos_incident > 0.0 {
+            (1.0 / ior, normal, cos_incident)
+        } else {
+            (ior, -normal, -cos_incident)
+        };
 
-        let k = 1.0 - eta * eta * (1.0 - dot_ni * dot_ni);
+        let k = 1.0 - eta * eta * (1.0 - cos_incident * cos_incident);
         if k < 0.0 {
-            self * eta
+            None
         } else {
-            self * eta - normal * (eta * dot_ni + k.sqrt())
-        }.normalized()
+            Some(
+                (self * eta + normal * (eta * cos_incident - k.sqrt()))
+                    .normalized(),
+            )
+        }
     }
 
     pub fn angle(&self, other: &Self) -> f64 {
         self.normalized().dot(&other.normalized()).acos()
     }
+
+    /// Get a component by index (0 = x, 1 = y, 2 = z)
+    pub fn axis(&self, index: usize) -> f64 {
+        match index {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("Vector3 axis index out of range: {}", index),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -217,6 +238,22 @@ mod tests {
     // assert!((refract.z as f32 - refract_glm.z) < EPSILON);
     // }
 
+    #[test]
+    fn refract_in_and_out() {
+        let normal = Vector3::new(0.0, 1.0, 0.0);
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalized();
+        // Snell's law going in: sin 45 = 1.5 sin t
+        let inside = incoming.refract(normal, 1.5).unwrap();
+        assert!((inside.x - 45f64.to_radians().sin() / 1.5).abs() < 1e-9);
+        assert!(inside.y < 0.0);
+        // Coming back out the other side gives the original direction
+        let outside = inside.refract(-normal, 1.5).unwrap();
+        assert!((outside - incoming).length() < 1e-9);
+        // Past the critical angle from the inside
+        let grazing = Vector3::new(1.0, 1.0, 0.0).normalized();
+        assert!(grazing.refract(normal, 1.5).is_none());
+    }
+
     #[test]
     fn angle() {
         let (a_glm, b_glm, a, b) = make_vectors();